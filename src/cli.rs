@@ -1,7 +1,9 @@
-use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 
 use crate::cmd;
+use crate::error::YageError;
 
 /// A simple tool to manage encrypted secrets in YAML files with age encryption
 #[derive(Parser, Debug)]
@@ -25,10 +27,34 @@ pub enum Commands {
     Edit(cmd::EditArgs),
     Encrypt(cmd::EncryptArgs),
     Env(cmd::EnvArgs),
+    Exec(cmd::ExecArgs),
+    Export(cmd::ExportArgs),
+    Helm(cmd::HelmArgs),
+    Import(cmd::ImportArgs),
+    K8sSecret(cmd::K8sSecretArgs),
+    Krm(cmd::KrmArgs),
     Keygen(cmd::KeygenArgs),
     Pubkey(cmd::PubkeyArgs),
     Recipients(cmd::RecipientsArgs),
+    Render(cmd::RenderArgs),
     ReEncrypt(cmd::ReEncryptArgs),
+    Rotate(cmd::RotateArgs),
+}
+
+/// An error in the arguments of the subcommand, reported like the errors detected by clap, with
+/// the usage of the subcommand.
+pub(crate) fn usage_error(
+    subcommand: &str,
+    kind: ErrorKind,
+    message: impl std::fmt::Display,
+) -> YageError {
+    let mut cli = Cli::command();
+    cli.build();
+    let error = match cli.find_subcommand_mut(subcommand) {
+        Some(command) => command.error(kind, message),
+        None => cli.error(kind, message),
+    };
+    YageError::Usage(error)
 }
 
 #[cfg(windows)]
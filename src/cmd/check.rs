@@ -1,17 +1,38 @@
-use std::io::Read;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 use clap::Args;
-use yaml_edit::{Document, YamlFile, YamlNode};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
 
-use crate::error::{Result, YageError};
-use crate::{EncryptionStatus, check_encrypted, check_recipients, stdin_or_file};
+use crate::error::Result;
+use crate::format::{FileFormat, SecretFile};
+use crate::{CommentSecretPolicy, EncryptionStatus};
 
 /// Check the encryption status of a YAML file
+///
+/// The JSON, TOML, dotenv and INI files are also supported. Their format is guessed from the
+/// file name: the `.json`, `.toml`, `.env` and `.ini` extensions, and the names starting with
+/// `.env` for the dotenv files. The other files are read as YAML, unless `--format` is used.
 #[derive(Args, Debug)]
 #[command(alias = "status")]
 pub struct CheckArgs {
+    /// What to do when a high-entropy token is detected in a comment
+    #[clap(
+        long,
+        value_name = "POLICY",
+        default_value_t = CommentSecretPolicy::Warn,
+        value_parser = PossibleValuesParser::new(["warn", "fail"])
+            .map(|s| CommentSecretPolicy::from_str(&s).unwrap()),
+    )]
+    pub on_comment_secret: CommentSecretPolicy,
+
+    /// The format of the files
+    ///
+    /// The format is guessed from the file name by default. YAML is used when the format
+    /// can't be guessed.
+    #[clap(long, value_name = "FORMAT")]
+    pub format: Option<FileFormat>,
+
     /// The YAML files to check
     #[arg()]
     pub files: Vec<PathBuf>,
@@ -20,35 +41,15 @@ pub struct CheckArgs {
 pub fn check(args: &CheckArgs) -> Result<i32> {
     let mut ok: bool = true;
     for file in &args.files {
-        debug!("loading yaml file: {file:?}");
-        // don't user read_yaml here, because we don't want it to print a warning if the
-        // recipients are not consistent
-        let mut s = String::new();
-        stdin_or_file(file)?.read_to_string(&mut s)?;
-        let doc = Document::from_str(&s).map_err(YageError::Yaml)?;
-        let input_data = doc
-            .as_mapping()
-            .map(YamlNode::Mapping)
-            .or_else(|| doc.as_sequence().map(YamlNode::Sequence))
-            .or_else(|| doc.as_scalar().map(YamlNode::Scalar))
-            .unwrap();
-        if let Ok(yaml_file) = YamlFile::from_str(&s) {
-            let leaks = crate::check_comments_for_secrets(&yaml_file);
-            for leak in &leaks {
-                warn!(
-                    "{}:{}:{}: high-entropy token detected (z-score: {})",
-                    file.to_string_lossy(),
-                    leak.line,
-                    leak.col,
-                    leak.z_score,
-                );
-            }
+        let secret_file = SecretFile::read(file, FileFormat::detect(file, args.format))?;
+        if secret_file.report_comment_secrets(file, args.on_comment_secret).is_err() {
+            ok = false;
         }
-        if !check_recipients(&input_data) {
+        if !secret_file.check_recipients() {
             error! {"{file:?}: inconsistent recipients"};
             ok = false;
         }
-        match check_encrypted(&input_data) {
+        match secret_file.encryption_status() {
             EncryptionStatus::Encrypted | EncryptionStatus::NoValue => (),
             EncryptionStatus::Mixed => {
                 error! {"{file:?}: partially encrypted"};
@@ -5,14 +5,22 @@ use clap::Args;
 
 use crate::cli::ENV_PATH_SEP;
 use crate::error::{Result, YageError};
-use crate::{
-    decrypt_yaml, load_identities, read_yaml_file, replace_document_root,
-    replace_yaml_file_document, write_yaml_file,
-};
+use crate::format::{FileFormat, SecretFile};
+use crate::{CommentSecretPolicy, load_identities};
 
 /// Decrypt the values in a YAML file
+///
+/// The JSON, TOML, dotenv and INI files are also supported. Their format is guessed from the
+/// file name: the `.json`, `.toml`, `.env` and `.ini` extensions, and the names starting with
+/// `.env` for the dotenv files. The other files are read as YAML, unless `--format` is used.
 #[derive(Args, Debug)]
 pub struct DecryptArgs {
+    /// Keep a backup of the overwritten files
+    ///
+    /// The previous version of each overwritten file is kept next to it, with a `.bak` suffix.
+    #[clap(short, long)]
+    pub backup: bool,
+
     /// Decrypt in place
     ///
     /// The input file is overwritten with the encrypted data.
@@ -47,6 +55,13 @@ pub struct DecryptArgs {
     )]
     pub key_files: Vec<PathBuf>,
 
+    /// The format of the files
+    ///
+    /// The format is guessed from the file name by default. YAML is used when the format
+    /// can't be guessed.
+    #[clap(long, value_name = "FORMAT")]
+    pub format: Option<FileFormat>,
+
     /// The output path to the decrypted YAML file
     ///
     /// The decrypted YAML file is written to the standard output by default.
@@ -70,24 +85,13 @@ pub fn decrypt(args: &DecryptArgs) -> Result<i32> {
         return Err(YageError::NoKeys);
     }
     for file in &args.files {
-        let (yaml_file, doc, input_data) = read_yaml_file(file)?;
-        let leaks = crate::check_comments_for_secrets(&yaml_file);
-        for leak in &leaks {
-            warn!(
-                "{}:{}:{}: high-entropy token detected (z-score: {})",
-                file.to_string_lossy(),
-                leak.line,
-                leak.col,
-                leak.z_score,
-            );
-        }
-        if !crate::check_recipients(&input_data) {
+        let mut secret_file = SecretFile::read(file, FileFormat::detect(file, args.format))?;
+        secret_file.report_comment_secrets(file, CommentSecretPolicy::Warn)?;
+        if !secret_file.check_recipients() {
             warn!("{}: inconsistent recipients", file.to_string_lossy());
         }
-        let output_data = decrypt_yaml(&input_data, &identities)?;
-        replace_document_root(&doc, &output_data);
-        replace_yaml_file_document(&yaml_file, &doc);
-        write_yaml_file(if args.in_place { file } else { &args.output }, &yaml_file)?;
+        secret_file.decrypt(&identities)?;
+        secret_file.write(if args.in_place { file } else { &args.output }, args.backup)?;
     }
     Ok(0)
 }
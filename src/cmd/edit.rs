@@ -1,19 +1,29 @@
+use std::collections::HashMap;
 use std::fs;
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
+use std::io::stdin;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
 
-use clap::Args;
-use tempfile::tempdir;
-use yaml_edit::YamlNode;
+use age::x25519;
+use clap::error::ErrorKind;
+use clap::{Args, ValueEnum};
+use serde_json::Value;
+use strum::{Display, EnumIs, EnumString};
+use toml_edit::{Item, Table};
+use yaml_edit::{Mapping, Sequence, YamlFile, YamlNode};
 
-use crate::cli::ENV_PATH_SEP;
+use crate::cli::{ENV_PATH_SEP, usage_error};
 use crate::error::{IOResultExt, Result, YageError};
+use crate::format::{
+    FileFormat, SecretFile, replace_value, toml_table_to_json, toml_value_to_json,
+};
+use crate::secret_dir::SecretDir;
 use crate::{
-    decrypt_yaml, encrypt_yaml, get_yaml_recipients, load_identities, map_set, read_yaml,
-    read_yaml_file, replace_document_root, replace_yaml_file_document, seq_set, write_yaml,
-    write_yaml_file,
+    CommentSecretPolicy, YageEncodedValue, decrypt_text, decrypt_yaml, encrypt_comment_secrets,
+    encrypt_text, encrypt_yaml, get_yaml_recipients, load_identities, load_recipients, map_set,
+    new_mut_cursor, parse_yaml_file, re_encrypt_comments, replace_comments, replace_document_root,
+    replace_yaml_file_document, report_comment_secrets, seq_set, write_yaml_file,
 };
 
 /// Edit an encrypted YAML file
@@ -23,15 +33,37 @@ use crate::{
 /// original file.
 ///
 /// The YAML file may contain some unencrypted values, and some encrypted values. The encrypted values
-/// are decrypted before the edition and all the values are encrypted after the edition.
+/// are decrypted before the edit and all the values are encrypted after the edit. The encrypted
+/// comments are decrypted too, and encrypted again when they are left unchanged.
+///
+/// Only the modified values are encrypted, the other values are left unchanged. When the
+/// recipients are changed with the --recipient, --recipient-file, --remove-recipient or
+/// --remove-recipient-file options, all the values are encrypted again.
+///
+/// If the file doesn't exist, it is created with the recipients passed with the --recipient and
+/// --recipient-file options.
 ///
-/// Only the modified values are encrypted, the other values are left unchanged.
+/// If the edited file is not a valid YAML file, the error is shown and the editor may be re-opened
+/// to fix it. The edit is only aborted when the answer is no, or when the standard input is closed.
+///
+/// JSON, TOML, dotenv and INI files are supported too. Their concurrent modifications are never merged.
+///
+/// The decrypted file is written in a private temporary directory, preferably in a memory-backed
+/// location ($XDG_RUNTIME_DIR or /dev/shm), so that the secrets are never written to a disk. A
+/// warning is shown when no memory-backed location is available. The file content is overwritten
+/// before its deletion.
 #[derive(Args, Debug)]
 pub struct EditArgs {
     /// The editor command to use
     #[clap(short, long, default_value = "vim", env = "EDITOR")]
     pub editor: String,
 
+    /// Keep a backup of the overwritten files
+    ///
+    /// The previous version of each overwritten file is kept next to it, with a `.bak` suffix.
+    #[clap(short, long)]
+    pub backup: bool,
+
     /// Decrypt with the specified key
     ///
     /// Note that passing private keys as arguments or environment variables may expose them to other users
@@ -58,79 +90,460 @@ pub struct EditArgs {
     )]
     pub key_files: Vec<PathBuf>,
 
-    /// The encrypted YAML file to edit
+    /// Add the recipient to the recipients of the file
+    ///
+    /// May be repeated.
+    #[clap(short, long = "recipient", value_name = "RECIPIENT")]
+    pub recipients: Vec<String>,
+
+    /// Add the recipients listed at PATH to the recipients of the file
+    ///
+    /// The recipients file is a text file with one recipient per line.
+    ///
+    /// May be repeated.
+    #[clap(short = 'R', long = "recipient-file", value_name = "FILE")]
+    pub recipient_files: Vec<PathBuf>,
+
+    /// Remove the recipient from the recipients of the file
+    ///
+    /// May be repeated.
+    #[clap(short = 'd', long = "remove-recipient", value_name = "RECIPIENT")]
+    pub remove_recipients: Vec<String>,
+
+    /// Remove the recipients in the file from the recipients of the file
+    ///
+    /// May be repeated.
+    #[clap(short = 'D', long = "remove-recipient-file", value_name = "FILE")]
+    pub remove_recipient_files: Vec<PathBuf>,
+
+    /// What to do when a high-entropy token is detected in a comment
+    ///
+    /// With `encrypt`, the comment content is replaced by an encrypted `yage[…]` value. Only the
+    /// comments of the YAML files can be encrypted.
+    #[clap(long, value_name = "POLICY", default_value_t = CommentSecretPolicy::Warn)]
+    pub on_comment_secret: CommentSecretPolicy,
+
+    /// What to do when the file is modified by another process during the edit
+    ///
+    /// With `merge`, the changes made during the edit are merged with the other changes, unless
+    /// the same value has been modified on both sides. With `abort`, or when the changes can't be
+    /// merged, the file is left untouched and the edited file is kept in its private temporary
+    /// directory.
+    ///
+    /// Only the changes of the YAML files are merged: the other formats are always handled like
+    /// with `abort`.
+    #[clap(long, value_name = "POLICY", default_value_t = ChangePolicy::Merge)]
+    pub on_change: ChangePolicy,
+
+    /// Create the temporary directory for the decrypted file in DIR
+    ///
+    /// By default, a memory-backed location is used when available.
+    #[clap(long, value_name = "DIR", env = "YAGE_TMPDIR")]
+    pub tmpdir: Option<PathBuf>,
+
+    /// The format of the file
+    ///
+    /// The format is guessed from the file name by default. YAML is used when the format
+    /// can't be guessed.
+    #[clap(long, value_name = "FORMAT")]
+    pub format: Option<FileFormat>,
+
+    /// The encrypted file to edit
+    ///
+    /// The file is created if it doesn't exist.
     #[arg()]
     pub file: PathBuf,
 }
 
+/// What to do when the edited file is modified concurrently
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, EnumIs, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ChangePolicy {
+    /// Merge the changes when they don't conflict
+    Merge,
+    /// Leave the file untouched
+    Abort,
+}
+
 pub fn edit(args: &EditArgs) -> Result<i32> {
     if args.file == Path::new("-") {
         return Err(YageError::InPlaceStdin);
     }
+    let format = FileFormat::detect(&args.file, args.format);
+    if !format.is_yaml() && args.on_comment_secret.is_encrypt() {
+        return Err(usage_error(
+            "edit",
+            ErrorKind::InvalidValue,
+            format!("the comments of the {format} files can't be encrypted"),
+        ));
+    }
     let identities = load_identities(&args.keys, &args.key_files)?;
-    if identities.is_empty() {
+    // keep the original content, to detect a modification of the file during the edit
+    let original_text = read_optional(&args.file)?;
+    if original_text.is_some() && identities.is_empty() {
         return Err(YageError::NoKeys);
     }
-    let (yaml_file, doc, input_data) = read_yaml_file(&args.file)?;
-    let leaks = crate::check_comments_for_secrets(&yaml_file);
-    for leak in &leaks {
-        warn!(
-            "{}:{}:{}: high-entropy token detected (z-score: {})",
-            args.file.to_string_lossy(),
-            leak.line,
-            leak.col,
-            leak.z_score,
-        );
-    }
-    if !crate::check_recipients(&input_data) {
-        warn!("{}: inconsistent recipients", args.file.to_string_lossy());
-    }
-    let recipients = get_yaml_recipients(&input_data)?;
-    if recipients.is_empty() {
-        return Err(YageError::NoRecipients);
+    if !format.is_yaml() {
+        return edit_secret_file(args, &identities, original_text, format);
     }
-    let previous_data = decrypt_yaml(&input_data, &identities)?;
-    // save the decrypted data in an editable temporary file. The file has the same name as the
-    // original file, but in a temporary directory. This way the user knows which file he is
-    // editing if its editor shows the file name.
-    let dir = tempdir()?;
-    #[cfg(unix)]
-    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
-    let filename =
-        args.file.file_name().ok_or(YageError::InvalidFileName { path: args.file.clone() })?;
-    let temp_file = dir.path().join(filename);
-    write_yaml(&temp_file, &previous_data)?;
-
-    run_editor(&args.editor, &temp_file)?;
-
-    let edited_data = read_yaml(&temp_file)?;
+    // the encrypted value of the comments decrypted in the edited buffer, by decrypted content
+    let mut encrypted_comments = HashMap::new();
+    // the original values, encrypted and decrypted, and the text to edit
+    let (original, buffer_text, file_recipients) = match &original_text {
+        Some(original_text) => {
+            let (original_file, _, input_data) = parse_yaml_file(original_text)?;
+            report_comment_secrets(&args.file, &original_file, args.on_comment_secret)?;
+            if !crate::check_recipients(&input_data) {
+                warn!("{}: inconsistent recipients", args.file.to_string_lossy());
+            }
+            let file_recipients = get_yaml_recipients(&input_data)?;
+            let previous_data = decrypt_yaml(&input_data, &identities)?;
+            // the edited buffer is the whole decrypted file, with its comments and formatting
+            let (buffer_file, buffer_doc, _) = parse_yaml_file(original_text)?;
+            replace_document_root(&buffer_doc, &previous_data);
+            replace_yaml_file_document(&buffer_file, &buffer_doc);
+            replace_comments(&buffer_file, |text| match YageEncodedValue::from_str(text) {
+                Ok(yev) => {
+                    let content = decrypt_text(&yev, &identities)?;
+                    encrypted_comments.insert(content.clone(), text.to_owned());
+                    Ok(Some(content))
+                }
+                Err(_) => Ok(None),
+            })?;
+            (Some((input_data, previous_data)), buffer_file.to_string(), file_recipients)
+        }
+        None => (None, String::new(), vec![]),
+    };
+    let (recipients, recipients_changed) = edit_recipients(args, &file_recipients)?;
+    let (mut dir, temp_file) = create_temp_file(args, &buffer_text)?;
+    let Some((yaml_file, doc, edited_data)) =
+        edit_temp_file(args, &mut dir, &temp_file, original.is_none(), parse_yaml_file)?
+    else {
+        return Ok(0);
+    };
     // Find what has not changed, and keep those values from the original
     // encrypted file unchanged. That data is encrypted with a nonce that
     // makes it appear different every time it is encrypted, so we avoid
     // encrypting it again. This way the data that has not changed isn't
     // changed in its encrypted form.
-    let to_encrypt_data = edited_data.clone();
-    apply_unchanged(&previous_data, &edited_data, &input_data, &to_encrypt_data)?;
+    // When the recipients have changed, all the values must be encrypted again.
+    let to_encrypt_data = match &original {
+        Some((input_data, previous_data)) if !recipients_changed => {
+            if !is_collection(input_data) && previous_data.yaml_eq(&edited_data) {
+                input_data.clone()
+            } else {
+                apply_unchanged(previous_data, &edited_data, input_data, &edited_data)?;
+                edited_data
+            }
+        }
+        _ => edited_data,
+    };
 
+    // the edited file is written back, so that all the changes in the comments are kept
     let output_data = encrypt_yaml(&to_encrypt_data, &recipients)?;
     replace_document_root(&doc, &output_data);
     replace_yaml_file_document(&yaml_file, &doc);
-    write_yaml_file(&args.file, &yaml_file)?;
+    let current_text = read_optional(&args.file)?;
+    let yaml_file = if current_text == original_text {
+        yaml_file
+    } else {
+        warn!("{}: file modified during the edit", args.file.to_string_lossy());
+        let merged = match (&original_text, &current_text) {
+            (Some(original_text), Some(current_text)) if args.on_change.is_merge() => {
+                merge_concurrent_changes(original_text, current_text, &output_data)?
+            }
+            _ => None,
+        };
+        match merged {
+            Some(yaml_file) => {
+                info!("{}: changes merged", args.file.to_string_lossy());
+                yaml_file
+            }
+            None => {
+                // keep the edited file in its private directory, so that the edits are not lost
+                dir.keep();
+                return Err(YageError::ConcurrentChange {
+                    path: args.file.clone(),
+                    edited: temp_file,
+                });
+            }
+        }
+    };
+    if recipients_changed {
+        re_encrypt_comments(&yaml_file, &identities, &recipients)?;
+    }
+    // the unchanged decrypted comments are encrypted again, in their original encrypted form when
+    // the recipients haven't changed
+    replace_comments(&yaml_file, |text| match encrypted_comments.get(text) {
+        Some(_) if recipients_changed => encrypt_text(text, &recipients).map(Some),
+        Some(encrypted) => Ok(Some(encrypted.clone())),
+        None => Ok(None),
+    })?;
+    if args.on_comment_secret.is_encrypt() {
+        encrypt_comment_secrets(&yaml_file, &recipients)?;
+    }
+    write_yaml_file(&args.file, &yaml_file, args.backup)?;
     Ok(0)
 }
 
+/// Edit a file in a format other than YAML. The concurrent changes of those files are not merged.
+fn edit_secret_file(
+    args: &EditArgs,
+    identities: &[x25519::Identity],
+    original_text: Option<String>,
+    format: FileFormat,
+) -> Result<i32> {
+    let (original, buffer_text, file_recipients) = match &original_text {
+        Some(original_text) => {
+            let input = SecretFile::parse(original_text, format)?;
+            input.report_comment_secrets(&args.file, args.on_comment_secret)?;
+            if !input.check_recipients() {
+                warn!("{}: inconsistent recipients", args.file.to_string_lossy());
+            }
+            let file_recipients = input.recipients()?;
+            let mut buffer = SecretFile::parse(original_text, format)?;
+            buffer.decrypt(identities)?;
+            let buffer_text = buffer.to_string();
+            (Some((input, buffer)), buffer_text, file_recipients)
+        }
+        None => (None, String::new(), vec![]),
+    };
+    let (recipients, recipients_changed) = edit_recipients(args, &file_recipients)?;
+    let (mut dir, temp_file) = create_temp_file(args, &buffer_text)?;
+    let Some(mut edited) =
+        edit_temp_file(args, &mut dir, &temp_file, original.is_none(), |text| {
+            SecretFile::parse(text, format)
+        })?
+    else {
+        return Ok(0);
+    };
+    // keep the unchanged values in their original encrypted form, like for the YAML files
+    if let Some((input, previous)) = &original
+        && !recipients_changed
+    {
+        match (previous, &mut edited, input) {
+            (SecretFile::Json(prev), SecretFile::Json(edited), SecretFile::Json(orig)) => {
+                edited.value = apply_unchanged_json(&prev.value, &edited.value, &orig.value);
+            }
+            (SecretFile::Toml(prev), SecretFile::Toml(edited), SecretFile::Toml(orig)) => {
+                apply_unchanged_toml(prev.as_table(), edited.as_table_mut(), orig.as_table());
+            }
+            (SecretFile::Dotenv(prev), SecretFile::Dotenv(edited), SecretFile::Dotenv(orig)) => {
+                edited.keep_unchanged(prev, orig);
+            }
+            _ => {}
+        }
+    }
+    edited.encrypt(&recipients)?;
+    if read_optional(&args.file)? != original_text {
+        warn!("{}: file modified during the edit", args.file.to_string_lossy());
+        if args.on_change.is_merge() {
+            warn!(
+                "{}: the changes of the {format} files can't be merged",
+                args.file.to_string_lossy()
+            );
+        }
+        dir.keep();
+        return Err(YageError::ConcurrentChange { path: args.file.clone(), edited: temp_file });
+    }
+    edited.write(&args.file, args.backup)?;
+    Ok(0)
+}
+
+/// The recipients of the edited file, and whether they differ from the current recipients of the
+/// file.
+fn edit_recipients(
+    args: &EditArgs,
+    file_recipients: &[x25519::Recipient],
+) -> Result<(Vec<x25519::Recipient>, bool)> {
+    let arg_recipients = load_recipients(&args.recipients, &args.recipient_files)?;
+    let remove_recipients = load_recipients(&args.remove_recipients, &args.remove_recipient_files)?;
+    let mut recipients = [arg_recipients, file_recipients.to_vec()].concat();
+    recipients.sort_by_cached_key(|r| r.to_string());
+    recipients.dedup();
+    recipients.retain(|r| !remove_recipients.contains(r));
+    debug!("{:?} recipients: {recipients:?}", args.file);
+    if recipients.is_empty() {
+        return Err(YageError::NoRecipients);
+    }
+    let recipients_changed = recipients.iter().any(|r| !file_recipients.contains(r))
+        || file_recipients.iter().any(|r| !recipients.contains(r));
+    Ok((recipients, recipients_changed))
+}
+
+/// Save the decrypted text in an editable temporary file.
+///
+/// The file has the same name as the original file, but in a temporary directory. This way the
+/// user knows which file he is editing if its editor shows the file name.
+fn create_temp_file(args: &EditArgs, text: &str) -> Result<(SecretDir, PathBuf)> {
+    let dir = SecretDir::new(args.tmpdir.as_deref())?;
+    let filename =
+        args.file.file_name().ok_or(YageError::InvalidFileName { path: args.file.clone() })?;
+    let temp_file = dir.path().join(filename);
+    fs::write(&temp_file, text).path_ctx(&temp_file)?;
+    Ok((dir, temp_file))
+}
+
+/// Run the editor on the temporary file until its content can be parsed.
+///
+/// Returns None when a new file is left empty.
+fn edit_temp_file<T>(
+    args: &EditArgs,
+    dir: &mut SecretDir,
+    temp_file: &Path,
+    new_file: bool,
+    parse: impl Fn(&str) -> Result<T>,
+) -> Result<Option<T>> {
+    loop {
+        run_editor(&args.editor, temp_file)?;
+        let content = read_edited_file(temp_file)?;
+        if new_file && content.trim().is_empty() {
+            info!("{}: empty file not created", args.file.to_string_lossy());
+            return Ok(None);
+        }
+        match parse(&content) {
+            Ok(edited) => return Ok(Some(edited)),
+            Err(e) => {
+                error!("{}: {e}", args.file.to_string_lossy());
+                if !confirm("Re-open the editor?")? {
+                    // keep the edited file in its private directory, so that the edits are not lost
+                    dir.keep();
+                    return Err(YageError::EditAborted { edited: temp_file.to_owned() });
+                }
+                insert_error_comment(temp_file, &e)?;
+            }
+        }
+    }
+}
+
+/// Read the file content, or None if the file doesn't exist.
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).path_ctx(path),
+    }
+}
+
+/// Merge the changes made during the edit with the changes made concurrently in the file.
+///
+/// The merge is done on the encrypted values: the values that were not modified during the
+/// edit keep their original encrypted form, so they can be compared to detect the changes
+/// on both sides. Returns None if the same value has been modified on both sides.
+fn merge_concurrent_changes(
+    original_text: &str,
+    current_text: &str,
+    output_data: &YamlNode,
+) -> Result<Option<YamlFile>> {
+    let (_, _, base) = parse_yaml_file(original_text)?;
+    let (yaml_file, doc, theirs) = parse_yaml_file(current_text)?;
+    if base.yaml_eq(&theirs) {
+        replace_document_root(&doc, output_data);
+    } else if !output_data.yaml_eq(&base) && !merge_changes(&base, output_data, &theirs) {
+        return Ok(None);
+    }
+    replace_yaml_file_document(&yaml_file, &doc);
+    Ok(Some(yaml_file))
+}
+
+/// Recursively apply the changes between `base` and `ours` to `theirs`.
+///
+/// Returns false if a conflicting change is found.
+fn merge_changes(base: &YamlNode, ours: &YamlNode, theirs: &YamlNode) -> bool {
+    if ours.yaml_eq(theirs) {
+        return true;
+    }
+    match (base, ours, theirs) {
+        (YamlNode::Mapping(base), YamlNode::Mapping(ours), YamlNode::Mapping(theirs)) => {
+            merge_mappings(base, ours, theirs)
+        }
+        (YamlNode::Sequence(base), YamlNode::Sequence(ours), YamlNode::Sequence(theirs)) => {
+            merge_sequences(base, ours, theirs)
+        }
+        _ => false,
+    }
+}
+
+fn merge_mappings(base: &Mapping, ours: &Mapping, theirs: &Mapping) -> bool {
+    let opt_eq = |a: &Option<YamlNode>, b: &Option<YamlNode>| match (a, b) {
+        (Some(a), Some(b)) => a.yaml_eq(b),
+        (None, None) => true,
+        _ => false,
+    };
+    let keys: Vec<_> = base.keys().chain(ours.keys()).collect();
+    for key in keys {
+        let base_val = base.get(key.clone());
+        let ours_val = ours.get(key.clone());
+        let theirs_val = theirs.get(key.clone());
+        if opt_eq(&ours_val, &base_val) || opt_eq(&ours_val, &theirs_val) {
+            continue;
+        }
+        if opt_eq(&theirs_val, &base_val) {
+            match ours_val {
+                Some(val) => map_set(theirs, key, new_mut_cursor(&val)),
+                None => {
+                    theirs.remove(key);
+                }
+            }
+        } else if let (Some(base_val), Some(ours_val), Some(theirs_val)) =
+            (base_val, ours_val, theirs_val)
+        {
+            if !merge_changes(&base_val, &ours_val, &theirs_val) {
+                return false;
+            }
+        } else {
+            return false;
+        }
+    }
+    true
+}
+
+/// Apply the changes of the items of `ours` to the matching items of `theirs`. The items of both
+/// sides are matched with the items of `base` by `align_sequences`.
+///
+/// Only the modified items are merged: an item inserted or removed in `ours` is a conflict.
+fn merge_sequences(base: &Sequence, ours: &Sequence, theirs: &Sequence) -> bool {
+    let value_eq = |a: &YamlNode, b: &YamlNode| a.yaml_eq(b);
+    let base: Vec<_> = base.into_iter().collect();
+    let ours: Vec<_> = ours.into_iter().collect();
+    let theirs_items: Vec<_> = theirs.into_iter().collect();
+    let ours_pairs = align_sequences(&base, &ours, value_eq);
+    if ours.len() != base.len()
+        || ours_pairs.len() != base.len()
+        || ours_pairs.iter().any(|(i, j)| i != j)
+    {
+        return false;
+    }
+    let theirs_pairs = align_sequences(&base, &theirs_items, value_eq);
+    for (i, (base_item, ours_item)) in base.iter().zip(&ours).enumerate() {
+        if ours_item.yaml_eq(base_item) {
+            continue;
+        }
+        let Some(&(_, j)) = theirs_pairs.iter().find(|(b, _)| *b == i) else {
+            // the item has been removed from theirs
+            return false;
+        };
+        if theirs_items[j].yaml_eq(base_item) {
+            seq_set(theirs, j, new_mut_cursor(ours_item));
+        } else if !merge_changes(base_item, ours_item, &theirs_items[j]) {
+            return false;
+        }
+    }
+    true
+}
+
 /// Recursively walk two value trees in tandem. For paths where the previous
 /// and edited values are equal, inject the original (encrypted) value from
 /// `original` into `target`.
+///
+/// The collections are always walked down to their values, and never replaced as a whole, so
+/// that the comments and formatting of the edited tree are kept.
 fn apply_unchanged(
     prev: &YamlNode,
     edited: &YamlNode,
     original: &YamlNode,
     target: &YamlNode,
 ) -> Result<()> {
-    if prev.yaml_eq(edited) {
-        return Ok(());
-    }
     if let YamlNode::Mapping(prev_m) = prev
         && let YamlNode::Mapping(edit_m) = edited
         && let YamlNode::Mapping(orig_m) = original
@@ -141,8 +554,8 @@ fn apply_unchanged(
                 && let Some(orig_val) = orig_m.get(key.clone())
                 && let Some(prev_val) = prev_m.get(key.clone())
             {
-                if prev_val.yaml_eq(&edit_val) {
-                    map_set(target_m, key, orig_val);
+                if prev_val.yaml_eq(&edit_val) && !is_collection(&orig_val) {
+                    map_set(target_m, key, new_mut_cursor(&orig_val));
                 } else if let Some(target_val) = target_m.get(key.clone()) {
                     apply_unchanged(&prev_val, &edit_val, &orig_val, &target_val)?;
                 }
@@ -153,22 +566,221 @@ fn apply_unchanged(
         && let YamlNode::Sequence(orig_s) = original
         && let YamlNode::Sequence(target_s) = target
     {
-        let len = prev_s.len().min(edit_s.len()).min(target_s.len()).min(orig_s.len());
-        for i in 0..len {
-            let prev_val = prev_s.get(i).unwrap();
-            let edit_val = edit_s.get(i).unwrap();
-            let orig_val = orig_s.get(i).unwrap();
-            if prev_val.yaml_eq(&edit_val) {
-                seq_set(target_s, i, orig_val);
-            } else {
-                let target_val = target_s.get(i).unwrap();
-                apply_unchanged(&prev_val, &edit_val, &orig_val, &target_val)?;
+        let prev_vals: Vec<_> = prev_s.values().collect();
+        let edit_vals: Vec<_> = edit_s.values().collect();
+        let orig_vals: Vec<_> = orig_s.values().collect();
+        for (i, j) in align_sequences(&prev_vals, &edit_vals, YamlNode::yaml_eq) {
+            let (prev_val, edit_val) = (&prev_vals[i], &edit_vals[j]);
+            let Some(orig_val) = orig_vals.get(i) else { continue };
+            if prev_val.yaml_eq(edit_val) && !is_collection(orig_val) {
+                seq_set(target_s, j, new_mut_cursor(orig_val));
+            } else if let Some(target_val) = target_s.get(j) {
+                apply_unchanged(prev_val, edit_val, orig_val, &target_val)?;
             }
         }
     }
     Ok(())
 }
 
+/// Build the JSON value to encrypt: the values that are equal in `prev` and `edited` are taken
+/// from `original`, in their encrypted form, and the other values from `edited`.
+fn apply_unchanged_json(prev: &Value, edited: &Value, original: &Value) -> Value {
+    match (prev, edited, original) {
+        (Value::Object(prev_m), Value::Object(edit_m), Value::Object(orig_m)) => Value::Object(
+            edit_m
+                .iter()
+                .map(|(key, edit_val)| {
+                    let value = match (prev_m.get(key), orig_m.get(key)) {
+                        (Some(prev_val), Some(orig_val)) => {
+                            apply_unchanged_json(prev_val, edit_val, orig_val)
+                        }
+                        _ => edit_val.clone(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        (Value::Array(prev_s), Value::Array(edit_s), Value::Array(orig_s)) => {
+            let mut values = edit_s.clone();
+            for (i, j) in align_sequences(prev_s, edit_s, Value::eq) {
+                if let Some(orig_val) = orig_s.get(i) {
+                    values[j] = apply_unchanged_json(&prev_s[i], &edit_s[j], orig_val);
+                }
+            }
+            Value::Array(values)
+        }
+        (Value::Object(_) | Value::Array(_), _, _) => edited.clone(),
+        _ if prev == edited => original.clone(),
+        _ => edited.clone(),
+    }
+}
+
+/// Take the values of the TOML table that are equal in `prev` and `edited` from `original`, in
+/// their encrypted form. The comments and the formatting of the edited table are kept.
+fn apply_unchanged_toml(prev: &Table, edited: &mut Table, original: &Table) {
+    for (key, edit_item) in edited.iter_mut() {
+        let (Some(prev_item), Some(orig_item)) = (prev.get(&key), original.get(&key)) else {
+            continue;
+        };
+        match (prev_item, edit_item, orig_item) {
+            (Item::Value(prev_val), Item::Value(edit_val), Item::Value(orig_val)) => {
+                apply_unchanged_toml_value(prev_val, edit_val, orig_val);
+            }
+            (Item::Table(prev_t), Item::Table(edit_t), Item::Table(orig_t)) => {
+                apply_unchanged_toml(prev_t, edit_t, orig_t);
+            }
+            (
+                Item::ArrayOfTables(prev_a),
+                Item::ArrayOfTables(edit_a),
+                Item::ArrayOfTables(orig_a),
+            ) => {
+                let prev_json: Vec<_> = prev_a.iter().map(toml_table_to_json).collect();
+                let edit_json: Vec<_> = edit_a.iter().map(toml_table_to_json).collect();
+                for (i, j) in align_sequences(&prev_json, &edit_json, Value::eq) {
+                    if let (Some(prev_t), Some(edit_t), Some(orig_t)) =
+                        (prev_a.get(i), edit_a.get_mut(j), orig_a.get(i))
+                    {
+                        apply_unchanged_toml(prev_t, edit_t, orig_t);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_unchanged_toml_value(
+    prev: &toml_edit::Value,
+    edited: &mut toml_edit::Value,
+    original: &toml_edit::Value,
+) {
+    use toml_edit::Value as TomlValue;
+    match (prev, edited, original) {
+        (
+            TomlValue::InlineTable(prev_t),
+            TomlValue::InlineTable(edit_t),
+            TomlValue::InlineTable(orig_t),
+        ) => {
+            for (key, edit_val) in edit_t.iter_mut() {
+                if let (Some(prev_val), Some(orig_val)) = (prev_t.get(&key), orig_t.get(&key)) {
+                    apply_unchanged_toml_value(prev_val, edit_val, orig_val);
+                }
+            }
+        }
+        (TomlValue::Array(prev_a), TomlValue::Array(edit_a), TomlValue::Array(orig_a)) => {
+            let prev_json: Vec<_> = prev_a.iter().map(toml_value_to_json).collect();
+            let edit_json: Vec<_> = edit_a.iter().map(toml_value_to_json).collect();
+            for (i, j) in align_sequences(&prev_json, &edit_json, Value::eq) {
+                if let (Some(prev_val), Some(edit_val), Some(orig_val)) =
+                    (prev_a.get(i), edit_a.get_mut(j), orig_a.get(i))
+                {
+                    apply_unchanged_toml_value(prev_val, edit_val, orig_val);
+                }
+            }
+        }
+        (TomlValue::InlineTable(_) | TomlValue::Array(_), _, _) => {}
+        (prev, edited, original) if toml_value_to_json(prev) == toml_value_to_json(edited) => {
+            replace_value(edited, original.clone());
+        }
+        _ => {}
+    }
+}
+
+/// Align the values of two sequences, so that the unchanged values are found even when they
+/// have been shifted by an insertion or a removal.
+///
+/// The equal values are matched with a longest common subsequence. The values left between two
+/// matches are then paired by position, as they are likely to be modified versions of each other.
+/// Returns the pairs of indices, in increasing order.
+fn align_sequences<T>(
+    prev: &[T],
+    edited: &[T],
+    value_eq: impl Fn(&T, &T) -> bool,
+) -> Vec<(usize, usize)> {
+    let (n, m) = (prev.len(), edited.len());
+    let eq: Vec<Vec<bool>> =
+        prev.iter().map(|p| edited.iter().map(|e| value_eq(p, e)).collect()).collect();
+    // lengths[i][j] is the length of the longest common subsequence of prev[i..] and edited[j..]
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if eq[i][j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    // start of the values not matched yet
+    let (mut gap_i, mut gap_j) = (0, 0);
+    while i < n && j < m {
+        if eq[i][j] {
+            pairs.extend((gap_i..i).zip(gap_j..j));
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+            (gap_i, gap_j) = (i, j);
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs.extend((gap_i..n).zip(gap_j..m));
+    pairs
+}
+
+fn is_collection(value: &YamlNode) -> bool {
+    matches!(value, YamlNode::Mapping(_) | YamlNode::Sequence(_))
+}
+
+/// Read the edited file, without the error comments inserted by `insert_error_comment`.
+fn read_edited_file(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path).path_ctx(path)?;
+    Ok(strip_error_comments(&content))
+}
+
+/// Remove the error comments inserted at the top of the edited file. The rest of the content is
+/// kept as is, with its line endings.
+fn strip_error_comments(content: &str) -> String {
+    let mut content = content;
+    while content.starts_with(ERROR_COMMENT_PREFIX) {
+        content = content.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+    content.to_owned()
+}
+
+/// Ask a question on the standard error output and read the answer on the standard input.
+///
+/// The default answer is yes, and the question is asked again until the answer is recognized.
+/// Returns false if the standard input is closed.
+fn confirm(question: &str) -> Result<bool> {
+    loop {
+        eprint!("{question} [Y/n] ");
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer)? == 0 {
+            eprintln!();
+            return Ok(false);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => eprintln!("Please answer yes or no."),
+        }
+    }
+}
+
+const ERROR_COMMENT_PREFIX: &str = "# yage error: ";
+
+/// Insert the error as a comment at the top of the edited file, in place of the previous error.
+fn insert_error_comment(path: &Path, error: &YageError) -> Result<()> {
+    let content = strip_error_comments(&fs::read_to_string(path).path_ctx(path)?);
+    let error = error.to_string().replace('\n', " ");
+    fs::write(path, format!("{ERROR_COMMENT_PREFIX}{error}\n{content}")).path_ctx(path)
+}
+
 fn run_editor(editor: &str, temp_file: &std::path::Path) -> Result<()> {
     let editor_process_res = Command::new(editor).arg(temp_file).spawn();
     let mut editor_process = match editor_process_res {
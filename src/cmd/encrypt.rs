@@ -5,15 +5,19 @@ use clap::Args;
 
 use crate::cli::ENV_PATH_SEP;
 use crate::error::{Result, YageError};
-use crate::{
-    encrypt_yaml, get_yaml_recipients, load_recipients, read_yaml_file, replace_document_root,
-    replace_yaml_file_document, write_yaml_file,
-};
+use crate::format::{FileFormat, SecretFile};
+use crate::{CommentSecretPolicy, load_recipients};
 
 /// Encrypt the values in a YAML file
 ///
 /// Only the values are encrypted, the keys are left in clear.
 ///
+/// The JSON, TOML, dotenv and INI files are also supported. Their format is guessed from the
+/// file name: the `.json`, `.toml`, `.env` and `.ini` extensions, and the names starting with
+/// `.env` for the dotenv files. The other files are read as YAML, unless `--format` is used.
+///
+/// The strings and numbers of the JSON and TOML files are encrypted in `"yage[…]"` strings.
+///
 /// The values are encrypted with the recipients' public keys in the age format,
 /// converted in base64 and surrounded by `yage[…]` markers.
 ///
@@ -22,6 +26,12 @@ use crate::{
 /// and left unchanged.
 #[derive(Args, Debug)]
 pub struct EncryptArgs {
+    /// Keep a backup of the overwritten files
+    ///
+    /// The previous version of each overwritten file is kept next to it, with a `.bak` suffix.
+    #[clap(short, long)]
+    pub backup: bool,
+
     /// Encrypt in place
     ///
     /// The input file is overwritten with the encrypted data.
@@ -60,6 +70,19 @@ pub struct EncryptArgs {
     )]
     pub recipient_files: Vec<PathBuf>,
 
+    /// The format of the files
+    ///
+    /// The format is guessed from the file name by default. YAML is used when the format
+    /// can't be guessed.
+    #[clap(long, value_name = "FORMAT")]
+    pub format: Option<FileFormat>,
+
+    /// What to do when a high-entropy token is detected in a comment
+    ///
+    /// With `encrypt`, the comment content is replaced by an encrypted `yage[…]` value.
+    #[clap(long, value_name = "POLICY", default_value_t = CommentSecretPolicy::Warn)]
+    pub on_comment_secret: CommentSecretPolicy,
+
     /// The output path to the encrypted YAML file
     ///
     /// The encrypted YAML file is written to the standard output by default.
@@ -82,21 +105,12 @@ pub fn encrypt(args: &EncryptArgs) -> Result<i32> {
     }
     let recipients = load_recipients(&args.recipients, &args.recipient_files)?;
     for file in &args.files {
-        let (yaml_file, doc, input_data) = read_yaml_file(file)?;
-        let leaks = crate::check_comments_for_secrets(&yaml_file);
-        for leak in &leaks {
-            warn!(
-                "{}:{}:{}: high-entropy token detected (z-score: {})",
-                file.to_string_lossy(),
-                leak.line,
-                leak.col,
-                leak.z_score,
-            );
-        }
-        if !crate::check_recipients(&input_data) {
+        let mut secret_file = SecretFile::read(file, FileFormat::detect(file, args.format))?;
+        secret_file.report_comment_secrets(file, args.on_comment_secret)?;
+        if !secret_file.check_recipients() {
             warn!("{}: inconsistent recipients", file.to_string_lossy());
         }
-        let yaml_recipients = get_yaml_recipients(&input_data)?;
+        let yaml_recipients = secret_file.recipients()?;
         let recipients = if recipients.is_empty() {
             &yaml_recipients
         } else if yaml_recipients.is_empty() || recipients == yaml_recipients {
@@ -104,10 +118,11 @@ pub fn encrypt(args: &EncryptArgs) -> Result<i32> {
         } else {
             return Err(YageError::InvalidRecipients);
         };
-        let output_data = encrypt_yaml(&input_data, recipients)?;
-        replace_document_root(&doc, &output_data);
-        replace_yaml_file_document(&yaml_file, &doc);
-        write_yaml_file(if args.in_place { file } else { &args.output }, &yaml_file)?;
+        secret_file.encrypt(recipients)?;
+        if args.on_comment_secret.is_encrypt() {
+            secret_file.encrypt_comment_secrets(recipients)?;
+        }
+        secret_file.write(if args.in_place { file } else { &args.output }, args.backup)?;
     }
     Ok(0)
 }
@@ -1,25 +1,81 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use clap::Args;
+use age::x25519;
+use clap::error::ErrorKind;
+use clap::{Args, ValueEnum};
+use strum::{Display, EnumString};
 use yaml_edit::YamlNode;
 
-use crate::cli::ENV_PATH_SEP;
-use crate::error::{Result, YageError};
-use crate::{decrypt_yaml, load_identities, read_yaml};
+use crate::cli::{ENV_PATH_SEP, usage_error};
+use crate::error::{IOResultExt, Result, YageError};
+use crate::format::{FileFormat, SecretFile, json_to_yaml};
+use crate::process::{exit_code, run_command, spawn_command, stop_command, termination_signal};
+use crate::{decrypt_yaml, load_identities, parse_yaml_file, read_yaml, yaml_to_json};
 
 /// Execute a command with the environment from the encrypted YAML file
 ///
+/// The environment may be read from several files with the --file option. The files are merged
+/// in order, the later files overriding the variables of the earlier ones.
+///
 /// The YAML file must contain a map with string keys and values. The keys are the environment
 /// variable names, and the values are the environment variable values.
-/// Other more complex YAML structures are not supported.
+///
+/// The JSON, TOML, dotenv and INI files are supported too, and detected from their file name. The
+/// INI sections are nested maps.
+///
+/// With --flatten, the nested maps are flattened: the names of the variables are built by joining
+/// the keys of the nested maps, so `database: {host: localhost}` gives `DATABASE_HOST=localhost`.
+/// The sequences are encoded in JSON.
+///
+/// On Unix, yage is replaced by the command, so that the command receives the signals sent to yage
+/// and its exit status is kept, unless --supervise is used.
 #[derive(Args, Debug)]
 pub struct EnvArgs {
     /// Start with an empty environment
     #[clap(short, long, default_value_t = false)]
     pub ignore_environment: bool,
 
+    /// Run the command as a child process instead of replacing yage with it
+    ///
+    /// The signals received by yage are forwarded to the command, and the exit code is the exit
+    /// code of the command, or 128 plus the signal number when the command is killed by a signal.
+    #[cfg(unix)]
+    #[clap(long)]
+    pub supervise: bool,
+
+    /// Restart the command when the environment read from the files changes
+    ///
+    /// The files are checked every --watch-interval seconds. When the decrypted environment
+    /// changes, the command is stopped with a SIGTERM signal, and started again with the new
+    /// environment. The command keeps running with the previous environment when the files can't
+    /// be decrypted. yage exits when the command exits by itself, or when it receives a
+    /// terminating signal, like SIGTERM, while the command is restarted.
+    ///
+    /// The command is always restarted: there is no way to only send it a signal, like SIGHUP, to
+    /// reload its configuration.
+    ///
+    /// The command runs as a child process, like with --supervise.
+    #[clap(long)]
+    pub watch: bool,
+
+    /// The interval between two checks of the watched files, in seconds
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value = "2",
+        value_parser = parse_interval,
+        requires = "watch"
+    )]
+    pub watch_interval: Duration,
+
+    #[command(flatten)]
+    pub env_options: EnvOptions,
+
     /// Decrypt with the specified key
     ///
     /// Note that passing private keys as arguments or environment variables may expose them to other users
@@ -46,45 +102,267 @@ pub struct EnvArgs {
     )]
     pub key_files: Vec<PathBuf>,
 
-    /// The YAML file to decrypt
-    #[arg()]
-    pub file: PathBuf,
+    /// Read the environment from FILE
+    ///
+    /// May be repeated. The files are merged in order: a variable defined in several files takes
+    /// its value from the last one.
+    ///
+    /// When this option is not used, the file is the first positional argument.
+    #[clap(short, long = "file", value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+
+    /// The YAML file to decrypt, unless --file is used
+    #[arg(value_name = "FILE", required_unless_present = "files")]
+    pub file: Option<String>,
+
+    /// The command to run, followed by its arguments
+    ///
+    /// The command may be separated from the options of yage with `--`.
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "COMMAND")]
+    pub command: Vec<String>,
+}
+
+/// The options to build the environment from the YAML files
+#[derive(Args, Debug)]
+pub struct EnvOptions {
+    /// Flatten the nested maps
+    #[clap(long)]
+    pub flatten: bool,
+
+    /// The separator between the keys of the flattened variable names
+    ///
+    /// The separator can't be empty or contain `=`.
+    #[clap(
+        long,
+        value_name = "SEP",
+        default_value = "_",
+        requires = "flatten",
+        value_parser = parse_separator,
+    )]
+    pub separator: String,
+
+    /// The case of the flattened variable names
+    #[clap(long, value_name = "CASE", default_value_t = NameCase::Upper, requires = "flatten")]
+    pub case: NameCase,
 
-    /// The command to run
-    #[arg()]
-    pub command: String,
+    /// Prefix the variable names with PREFIX
+    #[clap(long, value_name = "PREFIX", default_value = "")]
+    pub prefix: String,
 
-    /// The command arguments
-    #[arg()]
-    pub args: Vec<String>,
+    /// Only use the values under PATH
+    ///
+    /// PATH is a list of keys separated by dots, like `services.api`. The sequence items are
+    /// selected by their index.
+    #[clap(long, value_name = "PATH")]
+    pub path: Option<String>,
+
+    /// Fail when a variable is defined in several files
+    #[clap(long)]
+    pub strict: bool,
+}
+
+/// The case of the flattened variable names
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum NameCase {
+    /// Convert the names to upper case
+    Upper,
+    /// Convert the names to lower case
+    Lower,
+    /// Keep the case of the keys
+    Preserve,
 }
 
 pub fn env(args: &EnvArgs) -> Result<i32> {
+    let (files, command_name, command_args) =
+        files_and_command("env", &args.files, args.file.as_ref(), &args.command)?;
     let identities = load_identities(&args.keys, &args.key_files)?;
     if identities.is_empty() {
         return Err(YageError::NoKeys);
     }
-    let input_data = read_yaml(&args.file)?;
-    let output_data = decrypt_yaml(&input_data, &identities)?;
-    let env_data = build_env(&output_data)?;
-    for (key, value) in &env_data {
-        debug!("{key}={value}");
+    let env_data = load_env(&files, &identities, &args.env_options)?;
+    if args.watch {
+        return watch_env(args, &files, &identities, env_data, command_name, command_args);
     }
-    let mut command = Command::new(&args.command);
+    let mut command = env_command(args, &env_data, command_name, command_args);
+    #[cfg(unix)]
+    if !args.supervise {
+        use std::os::unix::process::CommandExt;
+        // exec only returns on error
+        return Err(command.exec()).path_ctx(command_name);
+    }
+    run_command(&mut command)
+}
+
+fn env_command(
+    args: &EnvArgs,
+    env_data: &HashMap<String, String>,
+    command_name: &str,
+    command_args: &[String],
+) -> Command {
+    let mut command = Command::new(command_name);
     if args.ignore_environment {
         command.env_clear();
     }
-    command.args(&args.args).envs(&env_data);
-    let status = command.spawn()?.wait()?;
-    Ok(status.code().unwrap_or(1))
+    command.args(command_args).envs(env_data);
+    command
+}
+
+/// Run the command, and restart it when the environment read from the files changes.
+fn watch_env(
+    args: &EnvArgs,
+    files: &[PathBuf],
+    identities: &[x25519::Identity],
+    mut env_data: HashMap<String, String>,
+    command_name: &str,
+    command_args: &[String],
+) -> Result<i32> {
+    let mut contents = read_files(files);
+    let mut child = spawn_command(&mut env_command(args, &env_data, command_name, command_args))?;
+    let mut last_check = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(exit_code(status));
+        }
+        thread::sleep(args.watch_interval.min(Duration::from_millis(100)));
+        if last_check.elapsed() < args.watch_interval {
+            continue;
+        }
+        last_check = Instant::now();
+        let new_contents = read_files(files);
+        if new_contents == contents {
+            continue;
+        }
+        contents = new_contents;
+        match load_env(files, identities, &args.env_options) {
+            Ok(new_env_data) if new_env_data == env_data => {
+                debug!("the files changed, but not the environment");
+            }
+            Ok(new_env_data) => {
+                info!("the environment changed — restarting {command_name}");
+                let status = stop_command(&mut child)?;
+                // yage has been asked to terminate while the command was stopped
+                if let Some(signal) = termination_signal() {
+                    debug!("signal {signal} received — {command_name} is not restarted");
+                    return Ok(exit_code(status));
+                }
+                env_data = new_env_data;
+                child =
+                    spawn_command(&mut env_command(args, &env_data, command_name, command_args))?;
+            }
+            Err(e) => warn!("can't read the environment — {command_name} is not restarted: {e}"),
+        }
+    }
+}
+
+/// The content of the files, to detect their changes.
+fn read_files(files: &[PathBuf]) -> Vec<Option<Vec<u8>>> {
+    files.iter().map(|file| fs::read(file).ok()).collect()
+}
+
+fn parse_interval(s: &str) -> std::result::Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("invalid interval: {s}"))
+}
+
+fn parse_separator(s: &str) -> std::result::Result<String, String> {
+    if s.is_empty() || s.contains(['=', '\0']) {
+        Err(format!("invalid separator: {s:?}"))
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+/// The files to decrypt and the command to run: the first positional argument is the file when
+/// no file is passed with --file, and the command otherwise.
+pub(crate) fn files_and_command<'a>(
+    subcommand: &str,
+    files: &[PathBuf],
+    file: Option<&'a String>,
+    command_line: &'a [String],
+) -> Result<(Vec<PathBuf>, &'a String, &'a [String])> {
+    let missing_command = || {
+        usage_error(subcommand, ErrorKind::MissingRequiredArgument, "no command to run was given")
+    };
+    match file {
+        Some(file) if files.is_empty() => {
+            let (command_name, command_args) =
+                command_line.split_first().ok_or_else(missing_command)?;
+            Ok((vec![PathBuf::from(file)], command_name, command_args))
+        }
+        Some(command_name) => Ok((files.to_vec(), command_name, command_line)),
+        None => {
+            let (command_name, command_args) =
+                command_line.split_first().ok_or_else(missing_command)?;
+            Ok((files.to_vec(), command_name, command_args))
+        }
+    }
+}
+
+/// Build the environment from the files, merged in order.
+pub(crate) fn load_env(
+    files: &[PathBuf],
+    identities: &[x25519::Identity],
+    options: &EnvOptions,
+) -> Result<HashMap<String, String>> {
+    let mut env_data = HashMap::new();
+    for file in files {
+        for (key, value) in file_env(file, identities, options)? {
+            if env_data.contains_key(&key) {
+                if options.strict {
+                    return Err(YageError::DuplicatedVariable { path: file.clone(), name: key });
+                }
+                debug!("{file:?}: overriding {key}");
+            }
+            env_data.insert(key, value);
+        }
+    }
+    for (key, value) in &env_data {
+        debug!("{key}={value}");
+    }
+    Ok(env_data)
+}
+
+/// Build the environment from a single file.
+fn file_env(
+    file: &Path,
+    identities: &[x25519::Identity],
+    options: &EnvOptions,
+) -> Result<HashMap<String, String>> {
+    let format = FileFormat::detect(file, None);
+    let output_data = if format.is_yaml() {
+        decrypt_yaml(&read_yaml(file)?, identities)?
+    } else {
+        // the other formats are converted to YAML, to build the environment the same way
+        let mut secret_file = SecretFile::read(file, format)?;
+        secret_file.decrypt(identities)?;
+        parse_yaml_file(&json_to_yaml(&secret_file.to_json()))?.2
+    };
+    let output_data = match &options.path {
+        Some(path) => select_path(&output_data, path)?,
+        None => output_data,
+    };
+    if options.flatten {
+        let mut env = HashMap::new();
+        match &output_data {
+            YamlNode::Mapping(_) => flatten_env(&output_data, None, options, &mut env),
+            _ => return Err(YageError::NotAMap),
+        }
+        Ok(env)
+    } else {
+        build_env(&output_data, &options.prefix)
+    }
 }
 
-fn build_env(data: &YamlNode) -> Result<HashMap<String, String>> {
+fn build_env(data: &YamlNode, prefix: &str) -> Result<HashMap<String, String>> {
     let mut env = HashMap::new();
     match data {
         YamlNode::Mapping(mapping) => {
             for (key, value) in mapping {
-                let key = plain_value_to_string(&key)?;
+                let key = format!("{prefix}{}", plain_value_to_string(&key)?);
                 let value = plain_value_to_string(&value)?;
                 env.insert(key, value);
             }
@@ -100,3 +378,64 @@ fn plain_value_to_string(data: &YamlNode) -> Result<String> {
         _ => Err(YageError::NotAStringOrNumber),
     }
 }
+
+/// Select the value at the dot separated path.
+fn select_path(data: &YamlNode, path: &str) -> Result<YamlNode> {
+    let not_found = || YageError::PathNotFound { path: path.to_owned() };
+    let mut value = data.clone();
+    for key in path.split('.') {
+        value = match &value {
+            YamlNode::Mapping(mapping) => mapping.get(key),
+            YamlNode::Sequence(sequence) => key.parse().ok().and_then(|i: usize| sequence.get(i)),
+            _ => None,
+        }
+        .ok_or_else(not_found)?;
+    }
+    Ok(value)
+}
+
+/// Add the values of the nested maps to the environment, with the keys joined in the names.
+fn flatten_env(
+    data: &YamlNode,
+    name: Option<&str>,
+    options: &EnvOptions,
+    env: &mut HashMap<String, String>,
+) {
+    match data {
+        YamlNode::Mapping(mapping) => {
+            for (key, value) in mapping {
+                let key = match &key {
+                    YamlNode::Scalar(scalar) => scalar.as_string(),
+                    _ => key.to_string(),
+                };
+                let key = env_name_part(&key, options.case);
+                let name = match name {
+                    Some(name) => format!("{name}{}{key}", options.separator),
+                    None => format!("{}{key}", options.prefix),
+                };
+                flatten_env(&value, Some(&name), options, env);
+            }
+        }
+        YamlNode::Scalar(scalar) => insert_env(env, name.unwrap_or_default(), scalar.as_string()),
+        _ => insert_env(env, name.unwrap_or_default(), yaml_to_json(data).to_string()),
+    }
+}
+
+fn insert_env(env: &mut HashMap<String, String>, name: &str, value: String) {
+    if env.insert(name.to_owned(), value).is_some() {
+        warn!("{name}: duplicated variable name");
+    }
+}
+
+/// Convert a key to a part of an environment variable name.
+///
+/// The characters other than ASCII letters, digits and underscores are replaced by underscores.
+fn env_name_part(key: &str, case: NameCase) -> String {
+    let part: String =
+        key.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    match case {
+        NameCase::Upper => part.to_uppercase(),
+        NameCase::Lower => part.to_lowercase(),
+        NameCase::Preserve => part,
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+use clap::Args;
+
+use crate::cli::ENV_PATH_SEP;
+use crate::cmd::{EnvOptions, files_and_command, load_env};
+use crate::error::{IOResultExt, Result, YageError};
+use crate::process::run_command;
+use crate::secret_dir::SecretDir;
+use crate::{create_private_file, load_identities};
+
+/// Execute a command with the decrypted values in files
+///
+/// Unlike the env command, the values are not passed to the command in its environment, where
+/// they could leak through /proc/PID/environ or the crash dumps. Each value is written in a file
+/// named after its variable, in a private temporary directory deleted when the command exits. The
+/// path of the directory is passed to the command in the YAGE_SECRETS_DIR environment variable.
+///
+/// The variables are built like in the env command, with the same --flatten, --path and --prefix
+/// options.
+///
+/// For example, `yage exec -K prod.key secrets.yaml -- sh -c 'psql -h db --password="$(cat
+/// $YAGE_SECRETS_DIR/password)"'`.
+#[derive(Args, Debug)]
+pub struct ExecArgs {
+    #[command(flatten)]
+    pub env_options: EnvOptions,
+
+    /// The environment variable containing the path of the directory
+    #[clap(long, value_name = "NAME", default_value = "YAGE_SECRETS_DIR")]
+    pub dir_var: String,
+
+    /// Create the temporary directory in DIR
+    ///
+    /// By default, a memory-backed location is used when available.
+    #[clap(long, value_name = "DIR", env = "YAGE_TMPDIR")]
+    pub tmpdir: Option<PathBuf>,
+
+    /// Write the values in named pipes that can only be read once
+    ///
+    /// The pipe is deleted as soon as the command has read it.
+    #[cfg(unix)]
+    #[clap(long)]
+    pub fifo: bool,
+
+    /// Decrypt with the specified key
+    ///
+    /// Note that passing private keys as arguments or environment variables may expose them to other users
+    /// on the system, and store them in your shell history. As a consequence the --key option and YAGE_KEY
+    /// environment variable should only be used in a secure environment.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY environment variable separated by commas.
+    #[clap(short, long = "key", value_name = "KEY", env = "YAGE_KEY", value_delimiter = ',')]
+    pub keys: Vec<String>,
+
+    /// Decrypt with the key in the file
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'K',
+        long = "key-file",
+        value_name = "FILE",
+        env = "YAGE_KEY_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub key_files: Vec<PathBuf>,
+
+    /// Read the values from FILE
+    ///
+    /// May be repeated. The files are merged in order: a variable defined in several files takes
+    /// its value from the last one.
+    ///
+    /// When this option is not used, the file is the first positional argument.
+    #[clap(short, long = "file", alias = "files", value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+
+    /// The YAML file to decrypt, unless --file is used
+    #[arg(value_name = "FILE", required_unless_present = "files")]
+    pub file: Option<String>,
+
+    /// The command to run, followed by its arguments
+    ///
+    /// The command may be separated from the options of yage with `--`.
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "COMMAND")]
+    pub command: Vec<String>,
+}
+
+pub fn exec(args: &ExecArgs) -> Result<i32> {
+    let (files, command_name, command_args) =
+        files_and_command("exec", &args.files, args.file.as_ref(), &args.command)?;
+    let identities = load_identities(&args.keys, &args.key_files)?;
+    if identities.is_empty() {
+        return Err(YageError::NoKeys);
+    }
+    let values = load_env(&files, &identities, &args.env_options)?;
+    if let Some(name) = values.keys().find(|name| !is_valid_file_name(name)) {
+        return Err(YageError::InvalidFileName { path: name.into() });
+    }
+    let dir = SecretDir::new(args.tmpdir.as_deref())?;
+    for (name, value) in values {
+        let path = dir.path().join(name);
+        #[cfg(unix)]
+        if args.fifo {
+            create_fifo(&path, value)?;
+            continue;
+        }
+        create_private_file(&path)?.write_all(value.as_bytes()).path_ctx(&path)?;
+    }
+    run_command(Command::new(command_name).args(command_args).env(&args.dir_var, dir.path()))
+}
+
+fn is_valid_file_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains(['/', '\\', '\0'])
+}
+
+/// Create a named pipe, and write the value in it once, in the background.
+#[cfg(unix)]
+fn create_fifo(path: &std::path::Path, value: String) -> Result<()> {
+    use std::ffi::CString;
+    use std::fs::{self, OpenOptions};
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| YageError::InvalidFileName { path: path.into() })?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error()).path_ctx(path);
+    }
+    let path = path.to_owned();
+    std::thread::spawn(move || {
+        // opening the pipe blocks until the command opens it for reading
+        match OpenOptions::new().write(true).open(&path) {
+            Ok(mut fifo) => {
+                if let Err(e) = fifo.write_all(value.as_bytes()) {
+                    warn!("{}: can't write the value: {e}", path.to_string_lossy());
+                }
+            }
+            Err(e) => warn!("{}: can't open the named pipe: {e}", path.to_string_lossy()),
+        }
+        // the value can only be read once
+        let _ = fs::remove_file(&path);
+    });
+    Ok(())
+}
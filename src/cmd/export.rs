@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+use strum::{Display, EnumString};
+
+use crate::cli::ENV_PATH_SEP;
+use crate::cmd::{EnvOptions, load_env};
+use crate::error::{Result, YageError};
+use crate::format::dotenv_double_quoted;
+use crate::{load_identities, stdout_or_private_file};
+
+/// Export the environment from the encrypted YAML file
+///
+/// The variables are built like with the env command, and written as a dotenv file, as shell
+/// code to be evaluated in a shell, as a docker --env-file file, or as a JSON object.
+///
+/// For example, `eval "$(yage export -K prod.key secrets.yaml)"` sets the variables in the
+/// current bash or zsh shell.
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// The output format
+    #[clap(short = 'F', long, value_name = "FORMAT", default_value_t = ExportFormat::Sh)]
+    pub format: ExportFormat,
+
+    #[command(flatten)]
+    pub env_options: EnvOptions,
+
+    /// Decrypt with the specified key
+    ///
+    /// Note that passing private keys as arguments or environment variables may expose them to other users
+    /// on the system, and store them in your shell history. As a consequence the --key option and YAGE_KEY
+    /// environment variable should only be used in a secure environment.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY environment variable separated by commas.
+    #[clap(short, long = "key", value_name = "KEY", env = "YAGE_KEY", value_delimiter = ',')]
+    pub keys: Vec<String>,
+
+    /// Decrypt with the key in the file
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'K',
+        long = "key-file",
+        value_name = "FILE",
+        env = "YAGE_KEY_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub key_files: Vec<PathBuf>,
+
+    /// The output path
+    ///
+    /// The variables are written to the standard output by default.
+    #[clap(short, long, default_value = "-", value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// The YAML files to decrypt
+    ///
+    /// The files are merged in order: a variable defined in several files takes its value from the
+    /// last one.
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+}
+
+/// The output format of the export command
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ExportFormat {
+    /// A dotenv file, with quoted values
+    Dotenv,
+    /// POSIX shell code
+    Sh,
+    /// fish shell code
+    Fish,
+    /// PowerShell code
+    Powershell,
+    /// A docker --env-file file, with raw values
+    Docker,
+    /// A JSON object
+    Json,
+}
+
+pub fn export(args: &ExportArgs) -> Result<i32> {
+    let identities = load_identities(&args.keys, &args.key_files)?;
+    if identities.is_empty() {
+        return Err(YageError::NoKeys);
+    }
+    let env_data = load_env(&args.files, &identities, &args.env_options)?;
+    // sort the variables for a stable output
+    let env_data: BTreeMap<_, _> = env_data.into_iter().collect();
+    let output = if args.format == ExportFormat::Json {
+        let mut json = serde_json::to_string_pretty(&env_data).expect("valid json");
+        json.push('\n');
+        json
+    } else {
+        let mut output = String::new();
+        for (name, value) in &env_data {
+            output.push_str(&format_variable(name, value, args.format)?);
+            output.push('\n');
+        }
+        output
+    };
+    stdout_or_private_file(&args.output)?.write_all(output.as_bytes())?;
+    Ok(0)
+}
+
+/// Format a variable assignment in the export format.
+fn format_variable(name: &str, value: &str, format: ExportFormat) -> Result<String> {
+    let unexportable =
+        || YageError::UnexportableVariable { name: name.to_owned(), format: format.to_string() };
+    if !is_valid_name(name) {
+        return Err(unexportable());
+    }
+    Ok(match format {
+        ExportFormat::Dotenv => {
+            if value.contains(['\'', '\n', '\r']) {
+                format!("{name}={}", dotenv_double_quoted(value))
+            } else {
+                format!("{name}='{value}'")
+            }
+        }
+        ExportFormat::Sh => {
+            format!("export {name}={}", shlex::try_quote(value).map_err(|_| unexportable())?)
+        }
+        ExportFormat::Fish => {
+            format!("set -gx {name} '{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+        }
+        ExportFormat::Powershell => format!("$Env:{name} = '{}'", value.replace('\'', "''")),
+        ExportFormat::Docker => {
+            // docker doesn't support any quoting in the env files
+            if value.contains(['\n', '\r']) {
+                return Err(unexportable());
+            }
+            format!("{name}={value}")
+        }
+        ExportFormat::Json => unreachable!("json is exported as a whole"),
+    })
+}
+
+/// Whether the name is a valid environment variable name for the shells.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
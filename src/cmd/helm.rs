@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use age::x25519;
+use clap::Args;
+use clap::error::ErrorKind;
+
+use crate::cli::{ENV_PATH_SEP, usage_error};
+use crate::error::{IOResultExt, Result, YageError};
+use crate::format::{FileFormat, SecretFile};
+use crate::load_identities;
+use crate::process::run_command;
+use crate::secret_dir::SecretDir;
+
+/// Run helm with decrypted values files
+///
+/// The values files passed to helm with the -f or --values options are decrypted in a private
+/// temporary directory, and helm is run with the decrypted files in place of the encrypted ones.
+/// The decrypted files are overwritten and deleted when helm exits.
+///
+/// The temporary directory is preferably created in a memory-backed location ($XDG_RUNTIME_DIR or
+/// /dev/shm), so that the secrets are never written to a disk.
+///
+/// For example, `yage helm -K prod.key upgrade api ./chart -f secrets.yaml` installs the chart with
+/// the decrypted values.
+#[derive(Args, Debug)]
+pub struct HelmArgs {
+    /// The helm command to run
+    #[clap(long, default_value = "helm", env = "YAGE_HELM", value_name = "COMMAND")]
+    pub helm: String,
+
+    /// Decrypt with the specified key
+    ///
+    /// Note that passing private keys as arguments or environment variables may expose them to other users
+    /// on the system, and store them in your shell history. As a consequence the --key option and YAGE_KEY
+    /// environment variable should only be used in a secure environment.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY environment variable separated by commas.
+    #[clap(short, long = "key", value_name = "KEY", env = "YAGE_KEY", value_delimiter = ',')]
+    pub keys: Vec<String>,
+
+    /// Decrypt with the key in the file
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'K',
+        long = "key-file",
+        value_name = "FILE",
+        env = "YAGE_KEY_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub key_files: Vec<PathBuf>,
+
+    /// Create the temporary directory for the decrypted files in DIR
+    ///
+    /// By default, a memory-backed location is used when available.
+    #[clap(long, value_name = "DIR", env = "YAGE_TMPDIR")]
+    pub tmpdir: Option<PathBuf>,
+
+    /// The helm arguments
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub args: Vec<String>,
+}
+
+pub fn helm(args: &HelmArgs) -> Result<i32> {
+    let identities = load_identities(&args.keys, &args.key_files)?;
+    let dir = SecretDir::new(args.tmpdir.as_deref())?;
+    let mut decrypted_files = 0;
+    let mut decrypt = |files: &str| -> Result<String> {
+        // helm accepts several comma separated files in a single option
+        let files = files
+            .split(',')
+            .map(|file| {
+                let path = Path::new(file);
+                let decrypted = dir.path().join(format!("{decrypted_files}-values.yaml"));
+                if decrypt_values_file(path, &decrypted, &identities)? {
+                    decrypted_files += 1;
+                    Ok(decrypted.to_string_lossy().into_owned())
+                } else {
+                    Ok(file.to_owned())
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(files.join(","))
+    };
+    let mut helm_args = vec![];
+    let mut args_iter = args.args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--" {
+            // the arguments after -- are not options
+            helm_args.push(arg.clone());
+            helm_args.extend(args_iter.by_ref().cloned());
+        } else if arg == "-f" || arg == "--values" {
+            helm_args.push(arg.clone());
+            if let Some(files) = args_iter.next() {
+                helm_args.push(decrypt(files)?);
+            }
+        } else if let Some(files) = arg.strip_prefix("--values=") {
+            helm_args.push(format!("--values={}", decrypt(files)?));
+        } else if let Some(files) = arg.strip_prefix("-f").filter(|f| !f.is_empty()) {
+            helm_args.push(format!("-f{}", decrypt(files)?));
+        } else {
+            helm_args.push(arg.clone());
+        }
+    }
+    debug!("decrypted values files: {decrypted_files}");
+    run_command(helm_command(&args.helm)?.args(&helm_args))
+}
+
+/// Decrypt the values file if it contains some encrypted values.
+///
+/// Returns false if the file is left as is: when it doesn't contain any encrypted value, or when
+/// it is not a local file.
+fn decrypt_values_file(
+    path: &Path,
+    decrypted: &Path,
+    identities: &[x25519::Identity],
+) -> Result<bool> {
+    if path == Path::new("-") || !path.is_file() {
+        return Ok(false);
+    }
+    let mut secret_file = SecretFile::read(path, FileFormat::Yaml)?;
+    if secret_file.encrypted_values().is_empty() {
+        return Ok(false);
+    }
+    if identities.is_empty() {
+        return Err(YageError::NoKeys);
+    }
+    debug!("decrypting {path:?} to {decrypted:?}");
+    secret_file.decrypt(identities)?;
+    fs::write(decrypted, secret_file.to_string()).path_ctx(decrypted)?;
+    Ok(true)
+}
+
+/// The helm command, split in words if needed.
+fn helm_command(helm: &str) -> Result<Command> {
+    let words = shlex::split(helm).unwrap_or_default();
+    let (program, args) = words.split_first().ok_or_else(|| {
+        usage_error("helm", ErrorKind::InvalidValue, format!("invalid helm command: {helm:?}"))
+    })?;
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
@@ -0,0 +1,153 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, ValueEnum};
+use strum::{Display, EnumString};
+use toml_edit::DocumentMut;
+
+use crate::cli::ENV_PATH_SEP;
+use crate::error::{Result, YageError};
+use crate::format::{DotenvFile, DotenvLine, FileFormat, json_to_yaml, toml_table_to_json};
+use crate::{
+    CommentSecretPolicy, encrypt_comment_secrets, encrypt_yaml, load_recipients, parse_yaml_file,
+    replace_document_root, replace_yaml_file_document, report_comment_secrets, stdin_or_file,
+    write_yaml_file, yaml_scalar,
+};
+
+/// Import a dotenv, JSON or TOML file in an encrypted YAML file
+///
+/// The input file is converted to YAML, and its values are encrypted like with the encrypt
+/// command. The comments of the dotenv files are kept in the YAML file.
+///
+/// The format of the input file is guessed from its extension when the --from option is not used.
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// The format of the input file
+    #[clap(long, value_name = "FORMAT")]
+    pub from: Option<ImportFormat>,
+
+    /// Encrypt to the specified recipients
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_RECIPIENT environment variable separated by commas.
+    #[clap(
+        short,
+        long = "recipient",
+        value_name = "RECIPIENT",
+        env = "YAGE_RECIPIENT",
+        value_delimiter = ','
+    )]
+    pub recipients: Vec<String>,
+
+    /// Encrypt to recipients listed at PATH
+    ///
+    /// The recipients file is a text file with one recipient per line.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_RECIPIENT_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'R',
+        long = "recipient-file",
+        value_name = "FILE",
+        env = "YAGE_RECIPIENT_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub recipient_files: Vec<PathBuf>,
+
+    /// What to do when a high-entropy token is detected in a comment
+    ///
+    /// With `encrypt`, the comment content is replaced by an encrypted `yage[…]` value.
+    #[clap(long, value_name = "POLICY", default_value_t = CommentSecretPolicy::Warn)]
+    pub on_comment_secret: CommentSecretPolicy,
+
+    /// The output path to the encrypted YAML file
+    ///
+    /// The encrypted YAML file is written to the standard output by default.
+    #[clap(short, long, default_value = "-", value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// The file to import
+    ///
+    /// If the filename is -, the file is read from the standard input.
+    #[arg()]
+    pub file: PathBuf,
+}
+
+/// The format of the imported file
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ImportFormat {
+    /// A dotenv file, with `KEY=value` lines
+    Dotenv,
+    /// A JSON file
+    Json,
+    /// A TOML file
+    Toml,
+}
+
+impl ImportFormat {
+    /// Guess the format from the file name, like for the encrypted files.
+    fn from_path(path: &Path) -> Option<ImportFormat> {
+        match FileFormat::from_name(path)? {
+            FileFormat::Json => Some(ImportFormat::Json),
+            FileFormat::Toml => Some(ImportFormat::Toml),
+            FileFormat::Dotenv => Some(ImportFormat::Dotenv),
+            FileFormat::Yaml | FileFormat::Ini => None,
+        }
+    }
+}
+
+pub fn import(args: &ImportArgs) -> Result<i32> {
+    let format = args
+        .from
+        .or_else(|| ImportFormat::from_path(&args.file))
+        .ok_or_else(|| YageError::UnknownFormat { path: args.file.clone() })?;
+    let recipients = load_recipients(&args.recipients, &args.recipient_files)?;
+    if recipients.is_empty() {
+        return Err(YageError::NoRecipients);
+    }
+    let mut input = String::new();
+    stdin_or_file(&args.file)?.read_to_string(&mut input)?;
+    let yaml_text = match format {
+        ImportFormat::Dotenv => dotenv_to_yaml(&input)?,
+        ImportFormat::Json => json_to_yaml(&serde_json::from_str(&input)?),
+        ImportFormat::Toml => {
+            json_to_yaml(&toml_table_to_json(input.parse::<DocumentMut>()?.as_table()))
+        }
+    };
+    debug!("converted yaml:\n{yaml_text}");
+    let (yaml_file, doc, input_data) = parse_yaml_file(&yaml_text)?;
+    report_comment_secrets(&args.file, &yaml_file, args.on_comment_secret)?;
+    let output_data = encrypt_yaml(&input_data, &recipients)?;
+    replace_document_root(&doc, &output_data);
+    replace_yaml_file_document(&yaml_file, &doc);
+    if args.on_comment_secret.is_encrypt() {
+        encrypt_comment_secrets(&yaml_file, &recipients)?;
+    }
+    write_yaml_file(&args.output, &yaml_file, false)?;
+    Ok(0)
+}
+
+/// Convert a dotenv file to a YAML text, keeping the comments and the blank lines.
+fn dotenv_to_yaml(input: &str) -> Result<String> {
+    let mut yaml = String::new();
+    for line in DotenvFile::parse_dotenv(input)?.lines() {
+        match line {
+            DotenvLine::Text(text) => yaml.push_str(text.trim()),
+            DotenvLine::Entry(entry) => {
+                yaml.push_str(&format!(
+                    "{}: {}",
+                    yaml_scalar(&entry.key),
+                    yaml_scalar(&entry.value)
+                ));
+                if let Some(comment) = entry.comment() {
+                    yaml.push_str(&format!(" #{}", comment.trim_end()));
+                }
+            }
+        }
+        yaml.push('\n');
+    }
+    Ok(yaml)
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use base64::prelude::*;
+use clap::{Args, ValueEnum};
+use strum::{Display, EnumString};
+
+use crate::cli::ENV_PATH_SEP;
+use crate::cmd::{EnvOptions, load_env};
+use crate::error::{Result, YageError};
+use crate::{load_identities, stdout_or_private_file, yaml_scalar};
+
+/// Generate a Kubernetes Secret manifest from the encrypted files
+///
+/// The entries of the secret are built like the variables of the env command, with the same
+/// --flatten, --path and --prefix options. The values are base64 encoded in the `data` field by
+/// default, or written in clear in the `stringData` field with --string-data.
+///
+/// For example, `yage k8s-secret -K prod.key --name api secrets.yaml | kubectl apply -f -`
+/// creates or updates the secret in the cluster.
+#[derive(Args, Debug)]
+pub struct K8sSecretArgs {
+    /// The name of the secret
+    #[clap(long, value_name = "NAME")]
+    pub name: String,
+
+    /// The namespace of the secret
+    #[clap(short, long, value_name = "NAMESPACE")]
+    pub namespace: Option<String>,
+
+    /// The type of the secret
+    ///
+    /// The kubernetes.io/tls secrets must have a `tls.crt` and a `tls.key` entry.
+    #[clap(long = "type", value_name = "TYPE", default_value_t = SecretType::Opaque)]
+    pub secret_type: SecretType,
+
+    /// Write the values in clear in the `stringData` field
+    #[clap(long)]
+    pub string_data: bool,
+
+    #[command(flatten)]
+    pub env_options: EnvOptions,
+
+    /// Decrypt with the specified key
+    ///
+    /// Note that passing private keys as arguments or environment variables may expose them to other users
+    /// on the system, and store them in your shell history. As a consequence the --key option and YAGE_KEY
+    /// environment variable should only be used in a secure environment.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY environment variable separated by commas.
+    #[clap(short, long = "key", value_name = "KEY", env = "YAGE_KEY", value_delimiter = ',')]
+    pub keys: Vec<String>,
+
+    /// Decrypt with the key in the file
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'K',
+        long = "key-file",
+        value_name = "FILE",
+        env = "YAGE_KEY_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub key_files: Vec<PathBuf>,
+
+    /// The output path
+    ///
+    /// The manifest is written to the standard output by default.
+    #[clap(short, long, default_value = "-", value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// The files to decrypt
+    ///
+    /// The files are merged in order: an entry defined in several files takes its value from the
+    /// last one.
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+}
+
+/// The type of a Kubernetes secret
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, ValueEnum)]
+pub enum SecretType {
+    /// A secret with arbitrary entries
+    #[strum(serialize = "Opaque")]
+    #[value(name = "Opaque")]
+    Opaque,
+    /// A TLS certificate and its key
+    #[strum(serialize = "kubernetes.io/tls")]
+    #[value(name = "kubernetes.io/tls")]
+    Tls,
+}
+
+impl SecretType {
+    /// The entries that must be in the secret.
+    fn required_keys(&self) -> &'static [&'static str] {
+        match self {
+            SecretType::Opaque => &[],
+            SecretType::Tls => &["tls.crt", "tls.key"],
+        }
+    }
+}
+
+pub fn k8s_secret(args: &K8sSecretArgs) -> Result<i32> {
+    let identities = load_identities(&args.keys, &args.key_files)?;
+    if identities.is_empty() {
+        return Err(YageError::NoKeys);
+    }
+    let entries = load_env(&args.files, &identities, &args.env_options)?;
+    // sort the entries for a stable output
+    let entries: BTreeMap<_, _> = entries.into_iter().collect();
+    if let Some(name) = entries.keys().find(|name| !is_valid_key(name)) {
+        return Err(YageError::InvalidSecretKey { name: name.clone() });
+    }
+    if let Some(name) = args.secret_type.required_keys().iter().find(|k| !entries.contains_key(**k))
+    {
+        return Err(YageError::MissingSecretKey {
+            name: name.to_string(),
+            secret_type: args.secret_type.to_string(),
+        });
+    }
+    let mut manifest = String::from("apiVersion: v1\nkind: Secret\nmetadata:\n");
+    manifest.push_str(&format!("  name: {}\n", yaml_scalar(&args.name)));
+    if let Some(namespace) = &args.namespace {
+        manifest.push_str(&format!("  namespace: {}\n", yaml_scalar(namespace)));
+    }
+    manifest.push_str(&format!("type: {}\n", yaml_scalar(&args.secret_type.to_string())));
+    if args.string_data {
+        manifest.push_str("stringData:");
+    } else {
+        manifest.push_str("data:");
+    }
+    if entries.is_empty() {
+        manifest.push_str(" {}");
+    }
+    manifest.push('\n');
+    for (name, value) in &entries {
+        let value =
+            if args.string_data { yaml_scalar(value) } else { BASE64_STANDARD.encode(value) };
+        manifest.push_str(&format!("  {}: {value}\n", yaml_scalar(name)));
+    }
+    stdout_or_private_file(&args.output)?.write_all(manifest.as_bytes())?;
+    Ok(0)
+}
+
+/// Whether the name is a valid key for a Kubernetes secret.
+fn is_valid_key(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "-._".contains(c))
+}
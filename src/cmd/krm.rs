@@ -0,0 +1,128 @@
+use std::io::{Read, Write, stdin, stdout};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use age::x25519;
+use base64::prelude::*;
+use clap::Args;
+use serde_json::Value;
+use yaml_edit::YamlNode;
+
+use crate::cli::ENV_PATH_SEP;
+use crate::error::{Result, YageError};
+use crate::format::{decrypt_json, json_to_yaml};
+use crate::{
+    YageEncodedValue, decrypt_value, indent_compact_sequences, load_identities, parse_yaml_file,
+    yaml_to_json,
+};
+
+/// Decrypt the resources of a KRM function ResourceList
+///
+/// This command implements the KRM function specification, to be used as an exec function in
+/// kustomize or kpt. A ResourceList is read on the standard input, the encrypted values of its
+/// resources are decrypted, and the ResourceList is written on the standard output.
+///
+/// The encrypted values in the `data` field of the Secret resources are base64 encoded after their
+/// decryption, as expected by Kubernetes.
+///
+/// The resources may be selected with the --kind and --name options, or with the `kinds` and
+/// `names` entries of the function config, a ConfigMap with comma separated values. All the
+/// resources are decrypted by default.
+#[derive(Args, Debug)]
+pub struct KrmArgs {
+    /// Decrypt the resources of this kind
+    ///
+    /// May be repeated.
+    #[clap(long = "kind", value_name = "KIND")]
+    pub kinds: Vec<String>,
+
+    /// Decrypt the resources with this name
+    ///
+    /// May be repeated.
+    #[clap(long = "name", value_name = "NAME")]
+    pub names: Vec<String>,
+
+    /// Decrypt with the specified key
+    ///
+    /// Note that passing private keys as arguments or environment variables may expose them to other users
+    /// on the system, and store them in your shell history. As a consequence the --key option and YAGE_KEY
+    /// environment variable should only be used in a secure environment.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY environment variable separated by commas.
+    #[clap(short, long = "key", value_name = "KEY", env = "YAGE_KEY", value_delimiter = ',')]
+    pub keys: Vec<String>,
+
+    /// Decrypt with the key in the file
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'K',
+        long = "key-file",
+        value_name = "FILE",
+        env = "YAGE_KEY_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub key_files: Vec<PathBuf>,
+}
+
+pub fn krm(args: &KrmArgs) -> Result<i32> {
+    let identities = load_identities(&args.keys, &args.key_files)?;
+    if identities.is_empty() {
+        return Err(YageError::NoKeys);
+    }
+    let mut input = String::new();
+    stdin().read_to_string(&mut input)?;
+    let (_, _, data) = parse_yaml_file(&indent_compact_sequences(&input))?;
+    let mut resource_list = yaml_to_json(&data);
+    if resource_list["kind"] != "ResourceList" {
+        return Err(YageError::NotAResourceList);
+    }
+    let config = &resource_list["functionConfig"]["data"];
+    let kinds = [args.kinds.clone(), config_list(config, "kinds")].concat();
+    let names = [args.names.clone(), config_list(config, "names")].concat();
+    if let Some(Value::Array(items)) = resource_list.get_mut("items") {
+        for item in items {
+            let selected = |field: &Value, values: &[String]| {
+                values.is_empty() || field.as_str().is_some_and(|v| values.iter().any(|s| s == v))
+            };
+            if selected(&item["kind"], &kinds) && selected(&item["metadata"]["name"], &names) {
+                debug!("decrypting {} {}", item["kind"], item["metadata"]["name"]);
+                *item = decrypt_resource(item, &identities)?;
+            }
+        }
+    }
+    stdout().write_all(json_to_yaml(&resource_list).as_bytes())?;
+    Ok(0)
+}
+
+/// The comma separated values of an entry of the function config.
+fn config_list(config: &Value, key: &str) -> Vec<String> {
+    config[key]
+        .as_str()
+        .map(|s| s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn decrypt_resource(resource: &Value, identities: &[x25519::Identity]) -> Result<Value> {
+    let mut resource = resource.clone();
+    if resource["kind"] == "Secret"
+        && let Some(Value::Object(data)) = resource.get_mut("data")
+    {
+        for value in data.values_mut() {
+            if let Value::String(s) = value
+                && YageEncodedValue::from_str(s).is_ok()
+            {
+                let plain_text = match decrypt_value(s, identities)? {
+                    YamlNode::Scalar(scalar) => scalar.as_string(),
+                    value => yaml_to_json(&value).to_string(),
+                };
+                *value = Value::String(BASE64_STANDARD.encode(plain_text));
+            }
+        }
+    }
+    decrypt_json(&resource, identities)
+}
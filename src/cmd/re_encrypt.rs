@@ -5,10 +5,8 @@ use clap::Args;
 
 use crate::cli::ENV_PATH_SEP;
 use crate::error::{Result, YageError};
-use crate::{
-    decrypt_yaml, encrypt_yaml, get_yaml_recipients, load_identities, load_recipients,
-    read_yaml_file, replace_document_root, replace_yaml_file_document, write_yaml_file,
-};
+use crate::format::{FileFormat, SecretFile};
+use crate::{CommentSecretPolicy, load_identities, load_recipients};
 
 /// Re-encrypt the values in a YAML file
 ///
@@ -23,6 +21,12 @@ use crate::{
 #[derive(Args, Debug)]
 #[command(alias = "recrypt")]
 pub struct ReEncryptArgs {
+    /// Keep a backup of the overwritten files
+    ///
+    /// The previous version of each overwritten file is kept next to it, with a `.bak` suffix.
+    #[clap(short, long)]
+    pub backup: bool,
+
     /// Re-encrypt in place
     ///
     /// The input file is overwritten with the encrypted data.
@@ -112,6 +116,19 @@ pub struct ReEncryptArgs {
     #[clap(short = 'D', long = "remove-recipient-file", value_name = "FILE")]
     pub remove_recipient_files: Vec<PathBuf>,
 
+    /// The format of the files
+    ///
+    /// The format is guessed from the file name by default. YAML is used when the format
+    /// can't be guessed.
+    #[clap(long, value_name = "FORMAT")]
+    pub format: Option<FileFormat>,
+
+    /// What to do when a high-entropy token is detected in a comment
+    ///
+    /// With `encrypt`, the comment content is replaced by an encrypted `yage[…]` value.
+    #[clap(long, value_name = "POLICY", default_value_t = CommentSecretPolicy::Warn)]
+    pub on_comment_secret: CommentSecretPolicy,
+
     /// The output path to the encrypted YAML file
     ///
     /// The encrypted YAML file is written to the standard output by default.
@@ -139,32 +156,22 @@ pub fn re_encrypt(args: &ReEncryptArgs) -> Result<i32> {
     let arg_recipients = load_recipients(&args.recipients, &args.recipient_files)?;
     let remove_recipients = load_recipients(&args.remove_recipients, &args.remove_recipient_files)?;
     for file in &args.files {
-        let (yaml_file, doc, input_data) = read_yaml_file(file)?;
-        let leaks = crate::check_comments_for_secrets(&yaml_file);
-        for leak in &leaks {
-            warn!(
-                "{}:{}:{}: high-entropy token detected (z-score: {})",
-                file.to_string_lossy(),
-                leak.line,
-                leak.col,
-                leak.z_score,
-            );
-        }
-        if !crate::check_recipients(&input_data) {
+        let mut secret_file = SecretFile::read(file, FileFormat::detect(file, args.format))?;
+        secret_file.report_comment_secrets(file, args.on_comment_secret)?;
+        if !secret_file.check_recipients() {
             warn!("{}: inconsistent recipients", file.to_string_lossy());
         }
-        let decrypted_data = decrypt_yaml(&input_data, &identities)?;
-        let yaml_recipients =
-            if args.keep_recipients { get_yaml_recipients(&input_data)? } else { vec![] };
+        let yaml_recipients = if args.keep_recipients { secret_file.recipients()? } else { vec![] };
         let mut recipients = [arg_recipients.clone(), yaml_recipients].concat();
         recipients.sort_by_cached_key(|r| r.to_string());
         recipients.dedup();
         recipients.retain(|r| !remove_recipients.contains(r));
         debug!("{file:?} recipients: {recipients:?}");
-        let output_data = encrypt_yaml(&decrypted_data, &recipients)?;
-        replace_document_root(&doc, &output_data);
-        replace_yaml_file_document(&yaml_file, &doc);
-        write_yaml_file(if args.in_place { file } else { &args.output }, &yaml_file)?;
+        secret_file.re_encrypt(&identities, &recipients)?;
+        if args.on_comment_secret.is_encrypt() {
+            secret_file.encrypt_comment_secrets(&recipients)?;
+        }
+        secret_file.write(if args.in_place { file } else { &args.output }, args.backup)?;
     }
     Ok(0)
 }
@@ -0,0 +1,276 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+use age::x25519;
+use clap::{Args, ValueEnum};
+use serde_json::Value;
+
+use crate::cli::ENV_PATH_SEP;
+use crate::error::{Result, YageError};
+use crate::format::{FileFormat, SecretFile, decrypt_json, select_json_path};
+use crate::{load_identities, stdin_or_file, stdout_or_private_file, yaml_scalar};
+
+/// Render a template with the decrypted values
+///
+/// The `{{ path.to.value }}` placeholders of the template are replaced by the values at these
+/// dot separated paths in the values files. Only the values used in the template are decrypted.
+///
+/// The placeholders may be followed by filters separated by `|`:
+///
+/// - `default("text")` replaces a missing or empty value by the text,
+///
+/// - `required` fails when the value is missing or empty,
+///
+/// - `raw`, `yaml`, `json` and `shell` escape the value for the given syntax, overriding the
+///   --escape option.
+///
+/// For example, `password: {{ db.password | yaml }}` is rendered as a valid YAML scalar.
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// The values files
+    ///
+    /// A value defined in several files is taken from the last one.
+    ///
+    /// May be repeated.
+    #[clap(short = 'f', long = "values", value_name = "FILE", required = true)]
+    pub values: Vec<PathBuf>,
+
+    /// The escaping mode of the values
+    #[clap(short, long, value_name = "MODE", default_value = "raw")]
+    pub escape: Escape,
+
+    /// Decrypt with the specified key
+    ///
+    /// Note that passing private keys as arguments or environment variables may expose them to other users
+    /// on the system, and store them in your shell history. As a consequence the --key option and YAGE_KEY
+    /// environment variable should only be used in a secure environment.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY environment variable separated by commas.
+    #[clap(short, long = "key", value_name = "KEY", env = "YAGE_KEY", value_delimiter = ',')]
+    pub keys: Vec<String>,
+
+    /// Decrypt with the key in the file
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'K',
+        long = "key-file",
+        value_name = "FILE",
+        env = "YAGE_KEY_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub key_files: Vec<PathBuf>,
+
+    /// The output path
+    ///
+    /// The rendered template is written to the standard output by default.
+    #[clap(short, long, default_value = "-", value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// The template to render
+    ///
+    /// The template is read from the standard input when the path is "-".
+    pub template: PathBuf,
+}
+
+/// How the values are escaped in the rendered template
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Escape {
+    /// The value as is
+    Raw,
+    /// A YAML scalar, quoted when needed
+    Yaml,
+    /// A JSON value, with the strings quoted
+    Json,
+    /// A shell word, quoted when needed
+    Shell,
+}
+
+pub fn render(args: &RenderArgs) -> Result<i32> {
+    let identities = load_identities(&args.keys, &args.key_files)?;
+    if identities.is_empty() {
+        return Err(YageError::NoKeys);
+    }
+    let values = args
+        .values
+        .iter()
+        .map(|path| Ok(SecretFile::read(path, FileFormat::detect(path, None))?.to_json()))
+        .collect::<Result<Vec<_>>>()?;
+    let mut template = String::new();
+    stdin_or_file(&args.template)?.read_to_string(&mut template)?;
+    let output = render_template(&template, &values, &identities, args.escape)?;
+    stdout_or_private_file(&args.output)?.write_all(output.as_bytes())?;
+    Ok(0)
+}
+
+fn render_template(
+    template: &str,
+    values: &[Value],
+    identities: &[x25519::Identity],
+    escape: Escape,
+) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let line = template[..template.len() - rest.len() + start].matches('\n').count() + 1;
+        let invalid = |message: &str| YageError::Template { line, message: message.into() };
+        // the placeholder ends at the first }} outside of a quoted literal
+        let body = &rest[start + 2..];
+        let end = unquoted_indices(body)
+            .into_iter()
+            .find(|&i| body[i..].starts_with("}}"))
+            .ok_or_else(|| invalid("unclosed placeholder"))?
+            + 2;
+        let expression = &rest[start + 2..start + end];
+        let rendered =
+            render_placeholder(expression, values, identities, escape).map_err(|e| match e {
+                YageError::Template { message, .. } => invalid(&message),
+                e => e,
+            })?;
+        output.push_str(&rendered);
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn render_placeholder(
+    expression: &str,
+    values: &[Value],
+    identities: &[x25519::Identity],
+    mut escape: Escape,
+) -> Result<String> {
+    let invalid = |message: String| YageError::Template { line: 0, message };
+    let mut parts = split_pipes(expression).into_iter().map(str::trim);
+    let head = parts.next().unwrap_or_default();
+    let mut value = if let Some(literal) = parse_literal(head)? {
+        // a quoted literal, like {{ "{{" }}
+        Some(Ok(Value::String(literal)))
+    } else if is_valid_path(head) {
+        // the last values file defining the path wins
+        values.iter().rev().find_map(|v| select_json_path(v, head)).map(|v| {
+            debug!("decrypting {head}");
+            decrypt_json(v, identities)
+        })
+    } else {
+        return Err(invalid(format!("invalid path {head:?}")));
+    }
+    .transpose()?;
+    for filter in parts {
+        let (name, arg) = match filter.split_once('(') {
+            Some((name, arg)) => {
+                let arg = arg
+                    .strip_suffix(')')
+                    .ok_or_else(|| invalid(format!("missing ) in {filter}")))?;
+                (name.trim(), Some(arg.trim()))
+            }
+            None => (filter, None),
+        };
+        match (name, arg) {
+            ("default", Some(arg)) => {
+                let default = parse_literal(arg)?
+                    .ok_or_else(|| invalid(format!("the default value must be quoted: {arg}")))?;
+                if is_empty(value.as_ref()) {
+                    value = Some(Value::String(default));
+                }
+            }
+            ("required", None) => {
+                if is_empty(value.as_ref()) {
+                    return Err(YageError::RequiredValue { path: head.to_owned() });
+                }
+            }
+            (name, arg) => match (Escape::from_str(name, false), arg) {
+                (Ok(mode), None) => escape = mode,
+                _ => return Err(invalid(format!("unknown filter {filter}"))),
+            },
+        }
+    }
+    escape_value(&value.unwrap_or(Value::Null), escape)
+}
+
+/// Split the expression on the `|` that are not in a quoted literal.
+fn split_pipes(expression: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    for i in unquoted_indices(expression) {
+        if expression[i..].starts_with('|') {
+            parts.push(&expression[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&expression[start..]);
+    parts
+}
+
+/// The positions of the characters of the expression that are not in a quoted literal.
+fn unquoted_indices(expression: &str) -> Vec<usize> {
+    let mut indices = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in expression.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, _) => indices.push(i),
+            _ => (),
+        }
+        escaped = false;
+    }
+    indices
+}
+
+/// Parse a double quoted literal, with the JSON escape sequences, or a single quoted literal.
+fn parse_literal(text: &str) -> Result<Option<String>> {
+    let invalid = || YageError::Template { line: 0, message: format!("invalid literal {text}") };
+    if text.starts_with('"') {
+        serde_json::from_str(text).map(Some).map_err(|_| invalid())
+    } else if let Some(text) = text.strip_prefix('\'') {
+        text.strip_suffix('\'').map(|s| Some(s.to_owned())).ok_or_else(invalid)
+    } else {
+        Ok(None)
+    }
+}
+
+fn is_valid_path(path: &str) -> bool {
+    path.split('.')
+        .all(|key| !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || "_-/".contains(c)))
+}
+
+fn is_empty(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => true,
+        Some(Value::String(s)) => s.is_empty(),
+        Some(_) => false,
+    }
+}
+
+fn escape_value(value: &Value, escape: Escape) -> Result<String> {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        // the maps and the lists are rendered in the JSON syntax, also valid in YAML
+        value => value.to_string(),
+    };
+    Ok(match (escape, value) {
+        (Escape::Raw, _) => text,
+        (Escape::Yaml, Value::Null) => "null".to_owned(),
+        (Escape::Yaml, Value::String(s)) => yaml_scalar(s),
+        (Escape::Yaml, _) => text,
+        (Escape::Json, value) => value.to_string(),
+        (Escape::Shell, _) => shlex::try_quote(&text)
+            .map_err(|_| YageError::UnsupportedValue {
+                value: text.clone(),
+                format: "shell".into(),
+            })?
+            .into_owned(),
+    })
+}
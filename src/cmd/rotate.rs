@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use crate::cli::ENV_PATH_SEP;
+use crate::error::{Result, YageError};
+use crate::format::{FileFormat, SecretFile};
+use crate::{CommentSecretPolicy, PendingWrite, load_identities, load_recipients};
+
+/// Re-encrypt all the encrypted files in a directory
+///
+/// The directories are searched recursively for YAML, JSON, TOML, dotenv and INI files containing
+/// some encrypted values. Their format is guessed from their name, like `.yaml`, `.json` or
+/// `.env.local`.
+/// The files ignored by git (in .gitignore files) and the .git directories are skipped.
+///
+/// The values of all the files found are decrypted and re-encrypted with the recipients of each
+/// file, plus the recipients added with the --recipient and --recipient-file options, minus the
+/// recipients removed with the --remove-recipient and --remove-recipient-file options. This
+/// rotates the data keys of all the values, and is especially useful when a recipient leaves a
+/// team, or when a recipient's key is compromised.
+///
+/// The operation is transactional: all the files are first re-encrypted in temporary files, and
+/// only moved to their final location once all of them have been successfully processed. If any
+/// file fails to decrypt, or can't be parsed while it contains some encrypted values, all the
+/// files are left untouched. Each file is then replaced atomically, but not all the files at once:
+/// an error while moving them in place, like a full disk, may leave some files rotated and the
+/// others not.
+#[derive(Args, Debug)]
+pub struct RotateArgs {
+    /// Keep a backup of the overwritten files
+    ///
+    /// The previous version of each overwritten file is kept next to it, with a `.bak` suffix.
+    #[clap(short, long)]
+    pub backup: bool,
+
+    /// Only show the files that would be re-encrypted
+    #[clap(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Decrypt with the specified key
+    ///
+    /// Note that passing private keys as arguments or environment variables may expose them to other users
+    /// on the system, and store them in your shell history. As a consequence the --key option and YAGE_KEY
+    /// environment variable should only be used in a secure environment.
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY environment variable separated by commas.
+    #[clap(short, long = "key", value_name = "KEY", env = "YAGE_KEY", value_delimiter = ',')]
+    pub keys: Vec<String>,
+
+    /// Decrypt with the key in the file
+    ///
+    /// May be repeated.
+    ///
+    /// Multiple values may be passed in the YAGE_KEY_FILE environment variable separated by the system path separator.
+    #[clap(
+        short = 'K',
+        long = "key-file",
+        value_name = "FILE",
+        env = "YAGE_KEY_FILE",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub key_files: Vec<PathBuf>,
+
+    /// Add the recipient to the recipients of each file
+    ///
+    /// May be repeated.
+    #[clap(short, long = "recipient", value_name = "RECIPIENT")]
+    pub recipients: Vec<String>,
+
+    /// Add the recipients listed at PATH to the recipients of each file
+    ///
+    /// The recipients file is a text file with one recipient per line.
+    ///
+    /// May be repeated.
+    #[clap(short = 'R', long = "recipient-file", value_name = "FILE")]
+    pub recipient_files: Vec<PathBuf>,
+
+    /// Remove the recipient from the recipients of each file
+    ///
+    /// May be repeated.
+    #[clap(short = 'd', long = "remove-recipient", value_name = "RECIPIENT")]
+    pub remove_recipients: Vec<String>,
+
+    /// Remove the recipients in the file from the recipients of each file
+    ///
+    /// May be repeated.
+    #[clap(short = 'D', long = "remove-recipient-file", value_name = "FILE")]
+    pub remove_recipient_files: Vec<PathBuf>,
+
+    /// The directories to search for encrypted files
+    #[arg(required = true)]
+    pub dirs: Vec<PathBuf>,
+}
+
+pub fn rotate(args: &RotateArgs) -> Result<i32> {
+    let identities = load_identities(&args.keys, &args.key_files)?;
+    if identities.is_empty() {
+        return Err(YageError::NoKeys);
+    }
+    let arg_recipients = load_recipients(&args.recipients, &args.recipient_files)?;
+    let remove_recipients = load_recipients(&args.remove_recipients, &args.remove_recipient_files)?;
+    let mut files = Vec::new();
+    for dir in &args.dirs {
+        files.extend(find_encrypted_files(dir)?);
+    }
+    // re-encrypt everything in temporary files first, so that no file is modified if one of them
+    // can't be processed
+    let mut rotated = Vec::with_capacity(files.len());
+    for file in &files {
+        let mut secret_file = SecretFile::read(file, FileFormat::detect(file, None))?;
+        secret_file.report_comment_secrets(file, CommentSecretPolicy::Warn)?;
+        let mut recipients = [arg_recipients.clone(), secret_file.recipients()?].concat();
+        recipients.sort_by_cached_key(|r| r.to_string());
+        recipients.dedup();
+        recipients.retain(|r| !remove_recipients.contains(r));
+        debug!("{file:?} recipients: {recipients:?}");
+        secret_file.re_encrypt(&identities, &recipients)?;
+        if args.dry_run {
+            info!(
+                "{}: would be re-encrypted to {} recipients",
+                file.to_string_lossy(),
+                recipients.len()
+            );
+        } else {
+            let pending_write = PendingWrite::new(file, secret_file.to_string().as_bytes())?;
+            rotated.push((file, pending_write, recipients.len()));
+        }
+    }
+    for (file, pending_write, recipients) in rotated {
+        pending_write.commit(args.backup)?;
+        info!("{}: re-encrypted to {recipients} recipients", file.to_string_lossy());
+    }
+    if args.dry_run {
+        info!("{} files would be re-encrypted", files.len());
+    } else {
+        info!("{} files re-encrypted", files.len());
+    }
+    Ok(0)
+}
+
+/// Find the files in a known format containing some encrypted values in a directory, recursively.
+///
+/// The hidden files, like `.env`, are included. The files ignored by git and the .git directories
+/// are skipped, as well as the files that can't be parsed, unless they look like they contain some
+/// encrypted values: the rotation fails then, instead of leaving them encrypted with the old keys.
+fn find_encrypted_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let walk = ignore::WalkBuilder::new(dir)
+        .require_git(false)
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    for entry in walk {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Some(format) = FileFormat::from_name(path) else { continue };
+        match SecretFile::read(path, format) {
+            Ok(secret_file) if !secret_file.encrypted_values().is_empty() => {
+                debug!("found encrypted file: {path:?}");
+                files.push(path.to_owned());
+            }
+            Ok(_) => debug!("skipping non encrypted file: {path:?}"),
+            Err(e) if fs::read_to_string(path).is_ok_and(|text| text.contains("yage[")) => {
+                return Err(YageError::InvalidEncryptedFile {
+                    path: path.into(),
+                    source: e.into(),
+                });
+            }
+            Err(e) => debug!("skipping invalid file: {path:?}: {e}"),
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,253 @@
+//! Deserialize the decrypted values of a YAML file with serde.
+//!
+//! The values are decrypted while they are deserialized, without writing the decrypted YAML text.
+//! The fields of type [`Secret`] are kept encrypted until they are accessed.
+//!
+//! ```
+//! use yage::x25519;
+//!
+//! let identity = x25519::Identity::generate();
+//! let mut doc = yage::Document::parse("port: 5432\n", yage::FileFormat::Yaml)?;
+//! doc.set("password", "s3cr3t")?;
+//! doc.encrypt(&yage::Vault::builder().identity(identity.clone()).build()?)?;
+//!
+//! let identities = [identity];
+//! let values: std::collections::BTreeMap<String, yage::Secret<String>> =
+//!     yage::from_str_decrypted(&doc.to_string(), &identities)?;
+//! assert_eq!(values["password"].decrypt(&identities)?, "s3cr3t");
+//! # Ok::<(), yage::error::YageError>(())
+//! ```
+
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use age::x25519;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::Value;
+use yaml_edit::YamlNode;
+
+use crate::error::{Result, YageError};
+use crate::format::decrypt_json;
+use crate::{YageEncodedValue, decrypt_value, parse_yaml_file, yaml_to_json};
+
+/// The name of the newtype struct of a [`Secret`], recognized by the [`Deserializer`].
+const SECRET: &str = "$yage::Secret";
+
+/// Deserialize an instance of `T` from a YAML text, with its encrypted values decrypted.
+pub fn from_str_decrypted<T: DeserializeOwned>(
+    text: &str,
+    identities: &[x25519::Identity],
+) -> Result<T> {
+    let (_, _, data) = parse_yaml_file(text)?;
+    T::deserialize(Deserializer::new(data, identities))
+}
+
+/// A deserializer over a YAML tree, which decrypts the encrypted values it reads
+pub struct Deserializer<'a> {
+    node: YamlNode,
+    identities: &'a [x25519::Identity],
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(node: YamlNode, identities: &'a [x25519::Identity]) -> Self {
+        Deserializer { node, identities }
+    }
+
+    /// The deserializer of the decrypted node, when the node is an encrypted value.
+    fn decrypted(self) -> Result<Self> {
+        match &self.node {
+            YamlNode::Scalar(scalar) if YageEncodedValue::from_str(&scalar.as_string()).is_ok() => {
+                if self.identities.is_empty() {
+                    return Err(YageError::NoKeys);
+                }
+                let node = decrypt_value(&scalar.as_string(), self.identities)?;
+                Ok(Deserializer { node, ..self })
+            }
+            _ => Ok(self),
+        }
+    }
+
+    fn child(&self, node: YamlNode) -> Self {
+        Deserializer { node, identities: self.identities }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'_> {
+    type Error = YageError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let de = self.decrypted()?;
+        match &de.node {
+            YamlNode::Mapping(mapping) => {
+                let entries: Vec<_> =
+                    mapping.iter().map(|(k, v)| (de.child(k), de.child(v))).collect();
+                visitor.visit_map(de::value::MapDeserializer::new(entries.into_iter()))
+            }
+            YamlNode::Sequence(sequence) => {
+                let items: Vec<_> = sequence.into_iter().map(|v| de.child(v)).collect();
+                visitor.visit_seq(de::value::SeqDeserializer::new(items.into_iter()))
+            }
+            node => Ok(yaml_to_json(node).deserialize_any(visitor)?),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // the value is not decrypted here, so that an optional secret is kept encrypted
+        match &self.node {
+            YamlNode::Scalar(_) if yaml_to_json(&self.node).is_null() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == SECRET {
+            // the secrets are kept encrypted
+            Ok(visitor.visit_newtype_struct(yaml_to_json(&self.node))?)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let de = self.decrypted()?;
+        match &de.node {
+            // a variant with a value, like `{variant: value}`, whose value is decrypted on access
+            YamlNode::Mapping(mapping) => {
+                let mut entries = mapping.iter();
+                match (entries.next(), entries.next()) {
+                    (Some((variant, value)), None) => {
+                        let variant = de.child(variant);
+                        visitor.visit_enum(Enum { variant, value: de.child(value) })
+                    }
+                    _ => Err(de::Error::invalid_value(
+                        de::Unexpected::Map,
+                        &"a map with a single variant",
+                    )),
+                }
+            }
+            node => Ok(yaml_to_json(node).deserialize_enum(name, variants, visitor)?),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// The variant of an enum, and its value
+struct Enum<'a> {
+    variant: Deserializer<'a>,
+    value: Deserializer<'a>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Enum<'a> {
+    type Error = YageError;
+    type Variant = Deserializer<'a>;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Deserializer<'a>)> {
+        Ok((seed.deserialize(self.variant)?, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'_> {
+    type Error = YageError;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, YageError> for Deserializer<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// A value kept encrypted until it is accessed
+///
+/// When deserialized with [`from_str_decrypted`], the value is kept as it is in the file, and is
+/// only decrypted by [`Secret::decrypt`]. With the other deserializers, the value is read in clear.
+#[derive(Clone, PartialEq)]
+pub struct Secret<T> {
+    value: Value,
+    marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Secret<T> {
+    /// Decrypt the value.
+    pub fn decrypt(&self, identities: &[x25519::Identity]) -> Result<T> {
+        Ok(serde_json::from_value(decrypt_json(&self.value, identities)?)?)
+    }
+}
+
+impl<T> Secret<T> {
+    /// The value, as stored in the file: the encrypted values are `yage[…]` strings.
+    pub fn encrypted(&self) -> &Value {
+        &self.value
+    }
+}
+
+impl<T> std::fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the value is never printed, even when it is not encrypted
+        write!(f, "Secret(…)")
+    }
+}
+
+impl<'de, T> de::Deserialize<'de> for Secret<T> {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct SecretVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for SecretVisitor<T> {
+            type Value = Secret<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a secret value")
+            }
+
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Self::Value, D::Error> {
+                let value = de::Deserialize::deserialize(deserializer)?;
+                Ok(Secret { value, marker: PhantomData })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(SECRET, SecretVisitor(PhantomData))
+    }
+}
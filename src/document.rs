@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use age::x25519;
+use serde_json::Value;
+
+use crate::error::{Result, YageError};
+use crate::format::{FileFormat, SecretFile, decrypt_json, select_json_path};
+use crate::{EncryptionStatus, Vault, write_text_file};
+
+/// A file with encrypted values, in one of the supported formats
+///
+/// The document is modified in place, and written back with the formatting and the comments of
+/// the original file. The values are addressed with dot separated paths, like `database.password`,
+/// and exchanged as JSON values.
+///
+/// ```
+/// use yage::FileFormat;
+/// use yage::{Document, Vault, x25519};
+///
+/// let vault = Vault::builder().identity(x25519::Identity::generate()).build()?;
+/// let mut doc = Document::parse("db:\n  user: app # the user\n", FileFormat::Yaml)?;
+/// doc.set("db.password", "s3cr3t")?;
+/// doc.encrypt(&vault)?;
+/// assert!(doc.to_string().contains("user: yage["));
+/// assert!(doc.to_string().contains("# the user"));
+/// assert_eq!(doc.get_decrypted("db.password", &vault)?, Some("s3cr3t".into()));
+/// # Ok::<(), yage::error::YageError>(())
+/// ```
+pub struct Document {
+    file: SecretFile,
+    format: FileFormat,
+    path: Option<PathBuf>,
+}
+
+impl Document {
+    /// Read the file, in the format guessed from its name.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::open_with_format(path, FileFormat::detect(path, None))
+    }
+
+    pub fn open_with_format(path: impl AsRef<Path>, format: FileFormat) -> Result<Self> {
+        let path = path.as_ref();
+        let file = SecretFile::read(path, format)?;
+        Ok(Document { file, format, path: Some(path.to_owned()) })
+    }
+
+    /// Parse the text of a document. The document has no path until it is saved with
+    /// [`Document::save_as`].
+    pub fn parse(text: &str, format: FileFormat) -> Result<Self> {
+        Ok(Document { file: SecretFile::parse(text, format)?, format, path: None })
+    }
+
+    pub fn format(&self) -> FileFormat {
+        self.format
+    }
+
+    /// The path the document was read from, or saved to.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The value at the path, as stored in the document: the encrypted values are `yage[…]`
+    /// strings.
+    pub fn get(&self, path: &str) -> Option<Value> {
+        select_json_path(&self.file.to_json(), path).cloned()
+    }
+
+    /// The decrypted value at the path. Only the values under the path are decrypted.
+    pub fn get_decrypted(&self, path: &str, vault: &Vault) -> Result<Option<Value>> {
+        self.get(path).map(|value| decrypt_json(&value, vault.identities())).transpose()
+    }
+
+    /// All the values of the document, as stored in the document.
+    pub fn to_value(&self) -> Value {
+        self.file.to_json()
+    }
+
+    /// All the values of the document, decrypted.
+    pub fn to_decrypted_value(&self, vault: &Vault) -> Result<Value> {
+        decrypt_json(&self.file.to_json(), vault.identities())
+    }
+
+    /// Set the value at the path. The last key of the path is added if needed.
+    ///
+    /// The value is stored in clear, until the document is encrypted.
+    pub fn set(&mut self, path: &str, value: impl Into<Value>) -> Result<()> {
+        self.file.set(path, &value.into())
+    }
+
+    /// Encrypt the values that are not encrypted yet.
+    ///
+    /// The values are encrypted to the recipients of the vault, which must match the recipients
+    /// of the already encrypted values.
+    pub fn encrypt(&mut self, vault: &Vault) -> Result<()> {
+        let document_recipients = self.file.recipients()?;
+        let recipients = if vault.recipients().is_empty() {
+            document_recipients
+        } else if document_recipients.is_empty() || document_recipients == vault.recipients() {
+            vault.recipients().to_vec()
+        } else {
+            return Err(YageError::InvalidRecipients);
+        };
+        self.file.encrypt(&default_recipients(recipients, vault)?)
+    }
+
+    /// Decrypt all the values.
+    pub fn decrypt(&mut self, vault: &Vault) -> Result<()> {
+        self.file.decrypt(vault.identities())
+    }
+
+    /// Encrypt all the values again, to the recipients of the vault if any, or to the current
+    /// recipients of the document.
+    pub fn re_encrypt(&mut self, vault: &Vault) -> Result<()> {
+        let recipients = if vault.recipients().is_empty() {
+            self.file.recipients()?
+        } else {
+            vault.recipients().to_vec()
+        };
+        self.file.re_encrypt(vault.identities(), &default_recipients(recipients, vault)?)
+    }
+
+    /// The recipients of the encrypted values.
+    pub fn recipients(&self) -> Result<Vec<x25519::Recipient>> {
+        self.file.recipients()
+    }
+
+    /// Whether the values of the document are encrypted.
+    pub fn check(&self) -> EncryptionStatus {
+        self.file.encryption_status()
+    }
+
+    /// Write the document back to its path.
+    pub fn save(&self) -> Result<()> {
+        let path = self.path.as_ref().ok_or(YageError::NoDocumentPath)?;
+        write_text_file(path, &self.file.to_string(), false)
+    }
+
+    /// Write the document to the path, which becomes the path of the document.
+    pub fn save_as(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        write_text_file(path, &self.file.to_string(), false)?;
+        self.path = Some(path.to_owned());
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)
+    }
+}
+
+impl std::fmt::Debug for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Document").field("format", &self.format).field("path", &self.path).finish()
+    }
+}
+
+/// The recipients, or the public keys of the identities of the vault when there are none.
+fn default_recipients(
+    recipients: Vec<x25519::Recipient>,
+    vault: &Vault,
+) -> Result<Vec<x25519::Recipient>> {
+    if !recipients.is_empty() {
+        Ok(recipients)
+    } else if !vault.identities().is_empty() {
+        Ok(vault.identities().iter().map(|identity| identity.to_public()).collect())
+    } else {
+        Err(YageError::NoRecipients)
+    }
+}
@@ -9,12 +9,38 @@ pub enum YageError {
     #[error("base64 encoding error {0}")]
     Base64Decode(#[from] base64::DecodeError),
 
+    #[error("{path:?}: high-entropy token detected in a comment")]
+    CommentSecret { path: PathBuf },
+
+    #[error(
+        "{path:?}: high-entropy token detected in a comment — the comments of the {format} files can't be encrypted"
+    )]
+    CommentSecretNotEncrypted { path: PathBuf, format: String },
+
+    #[error("{path:?} was modified during the edit — the edited file is kept in {edited:?}")]
+    ConcurrentChange { path: PathBuf, edited: PathBuf },
+
     #[error("age decryption error: {0}")]
     Decrypt(#[from] age::DecryptError),
 
+    #[error("deserialization error: {0}")]
+    Deserialize(String),
+
     #[error("age encryption error: {0}")]
     Encrypt(#[from] age::EncryptError),
 
+    #[error("edit aborted — the edited file is kept in {edited:?}")]
+    EditAborted { edited: PathBuf },
+
+    #[error("invalid dotenv line {line}: {message}")]
+    Dotenv { line: usize, message: String },
+
+    #[error("invalid INI line {line}: {message}")]
+    Ini { line: usize, message: String },
+
+    #[error("{path:?}: variable {name} already defined in a previous file")]
+    DuplicatedVariable { path: PathBuf, name: String },
+
     #[error("editor exited with an error status")]
     Editor,
 
@@ -27,6 +53,9 @@ pub enum YageError {
     #[error("invalid file name: {path:?}")]
     InvalidFileName { path: PathBuf },
 
+    #[error("{path:?}: invalid file with encrypted values: {source}")]
+    InvalidEncryptedFile { path: PathBuf, source: Box<YageError> },
+
     #[error(
         "invalid number of input files — consider using --in-place to work with multiple files"
     )]
@@ -35,6 +64,9 @@ pub enum YageError {
     #[error("the recipients form the command line don't match the recipients from the file")]
     InvalidRecipients,
 
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
     #[error("invalid value encoding")]
     InvalidValueEncoding,
 
@@ -47,6 +79,9 @@ pub enum YageError {
     #[error("no recipients provided")]
     NoRecipients,
 
+    #[error("the document has no path — use save_as to choose one")]
+    NoDocumentPath,
+
     #[error("yaml value is not a map")]
     NotAMap,
 
@@ -56,15 +91,52 @@ pub enum YageError {
     #[error("passphrase not supported")]
     PassphraseUnsupported,
 
+    #[error("path not found in the YAML file: {path}")]
+    PathNotFound { path: String },
+
     #[error("{path}: {source}")]
     PathIo { path: PathBuf, source: std::io::Error },
 
     #[error("can't parse recipient {recipient}: {message}")]
     RecipientParse { recipient: String, message: String },
 
+    #[error("TOML error: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+
+    #[error("{value:?}: value can't be represented in the {format} format")]
+    UnsupportedValue { value: String, format: String },
+
+    #[error("the input is not a KRM ResourceList")]
+    NotAResourceList,
+
+    #[error("{name}: invalid key for a Kubernetes secret")]
+    InvalidSecretKey { name: String },
+
+    #[error("{name}: key required in a {secret_type} secret")]
+    MissingSecretKey { name: String, secret_type: String },
+
+    #[error("invalid template line {line}: {message}")]
+    Template { line: usize, message: String },
+
+    #[error("{path}: required value missing or empty")]
+    RequiredValue { path: String },
+
+    #[error("{path:?}: unknown file format — use --from to specify it")]
+    UnknownFormat { path: PathBuf },
+
+    #[error("{name}: variable can't be exported in the {format} format")]
+    UnexportableVariable { name: String, format: String },
+
+    /// An invalid command line, detected after its parsing by clap
+    #[error(transparent)]
+    Usage(#[from] clap::Error),
+
     #[error(transparent)]
     Utf8(#[from] std::string::FromUtf8Error),
 
+    #[error("directory walk error: {0}")]
+    Walk(#[from] ignore::Error),
+
     #[error("YAML error: {0}")]
     Yaml(#[from] YamlError),
 }
@@ -81,3 +153,9 @@ impl<T> IOResultExt<T> for io::Result<T> {
         self.map_err(|source| YageError::PathIo { source, path: path.into() })
     }
 }
+
+impl serde::de::Error for YageError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        YageError::Deserialize(msg.to_string())
+    }
+}
@@ -0,0 +1,442 @@
+use std::str::FromStr;
+
+use age::x25519;
+use serde_json::{Map, Value};
+use yaml_edit::YamlNode;
+
+use crate::error::{Result, YageError};
+use crate::{
+    EncryptionStatus, YageEncodedValue, combine_encryption_status, decrypt_value, encrypt_text,
+    yaml_scalar, yaml_to_json,
+};
+
+/// A dotenv or INI file: a list of `key=value` entries, with comments and blank lines.
+///
+/// The entries keep the original text of their values, so that the file is written back like it
+/// was read. Only the modified values are formatted again.
+#[derive(Debug, Clone)]
+pub struct DotenvFile {
+    ini: bool,
+    lines: Vec<DotenvLine>,
+    final_newline: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum DotenvLine {
+    /// A comment, a blank line or an INI section header
+    Text(String),
+    Entry(DotenvEntry),
+}
+
+#[derive(Debug, Clone)]
+pub struct DotenvEntry {
+    /// The INI section of the entry
+    pub section: Option<String>,
+    pub key: String,
+    pub value: String,
+    /// The text before the value, like `export KEY=`
+    head: String,
+    /// The value as written in the file, with its quotes
+    raw: String,
+    /// The text after the value, like ` # comment`
+    tail: String,
+}
+
+impl DotenvEntry {
+    /// The comment at the end of the entry line, without the `#`.
+    pub fn comment(&self) -> Option<&str> {
+        self.tail.trim_start().strip_prefix('#')
+    }
+}
+
+impl DotenvFile {
+    /// Parse a dotenv file.
+    ///
+    /// The values may be unquoted, single quoted or double quoted, and the quoted values may span
+    /// several lines. The escape sequences are only interpreted in the double quoted values.
+    pub fn parse_dotenv(text: &str) -> Result<Self> {
+        let mut lines = vec![];
+        let mut text_lines = text.lines().enumerate();
+        while let Some((i, line)) = text_lines.next() {
+            let invalid =
+                |message: &str| YageError::Dotenv { line: i + 1, message: message.into() };
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                lines.push(DotenvLine::Text(line.to_owned()));
+                continue;
+            }
+            let (key, rest) = line.split_once('=').ok_or_else(|| invalid("missing ="))?;
+            let key = key.trim();
+            let key = key.strip_prefix("export ").unwrap_or(key).trim();
+            if !is_valid_key(key) {
+                return Err(invalid("invalid variable name"));
+            }
+            let value_text = rest.trim_start();
+            let head = line[..line.len() - value_text.len()].to_owned();
+            let (value, raw, tail) = match value_text.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    // the quoted value may continue on the next lines
+                    let mut text = value_text[1..].to_owned();
+                    let end = loop {
+                        if let Some(end) = closing_quote(&text, quote) {
+                            break end;
+                        }
+                        let (_, next) =
+                            text_lines.next().ok_or_else(|| invalid("unterminated quote"))?;
+                        text.push('\n');
+                        text.push_str(next);
+                    };
+                    let value =
+                        if quote == '"' { unescape(&text[..end]) } else { text[..end].into() };
+                    let tail = &text[end + 1..];
+                    if !tail.trim().is_empty() && !tail.trim().starts_with('#') {
+                        return Err(invalid("unexpected text after the quoted value"));
+                    }
+                    (value, format!("{quote}{}", &text[..=end]), tail.to_owned())
+                }
+                _ => {
+                    let end = value_text.find(" #").unwrap_or(value_text.len());
+                    let value = value_text[..end].trim_end();
+                    (value.to_owned(), value.to_owned(), value_text[value.len()..].to_owned())
+                }
+            };
+            let entry = DotenvEntry { section: None, key: key.to_owned(), value, head, raw, tail };
+            lines.push(DotenvLine::Entry(entry));
+        }
+        Ok(DotenvFile { ini: false, lines, final_newline: text.ends_with('\n') })
+    }
+
+    /// Parse an INI file.
+    ///
+    /// The keys are separated from their values by `=` or `:`. The values may be quoted, but the
+    /// escape sequences and the comments at the end of the lines are not supported.
+    pub fn parse_ini(text: &str) -> Result<Self> {
+        let mut lines = vec![];
+        let mut section = None;
+        for (i, line) in text.lines().enumerate() {
+            let invalid = |message: &str| YageError::Ini { line: i + 1, message: message.into() };
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with([';', '#']) {
+                lines.push(DotenvLine::Text(line.to_owned()));
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix('[') {
+                let name = name.strip_suffix(']').ok_or_else(|| invalid("missing ]"))?;
+                section = Some(name.trim().to_owned());
+                lines.push(DotenvLine::Text(line.to_owned()));
+                continue;
+            }
+            let separator = line.find(['=', ':']).ok_or_else(|| invalid("missing ="))?;
+            let key = line[..separator].trim();
+            if key.is_empty() {
+                return Err(invalid("missing key"));
+            }
+            let value_text = line[separator + 1..].trim_start();
+            let raw = value_text.trim_end();
+            let value = match raw.chars().next() {
+                Some(quote @ ('"' | '\'')) if raw.len() > 1 && raw.ends_with(quote) => {
+                    &raw[1..raw.len() - 1]
+                }
+                _ => raw,
+            };
+            lines.push(DotenvLine::Entry(DotenvEntry {
+                section: section.clone(),
+                key: key.to_owned(),
+                value: value.to_owned(),
+                head: line[..line.len() - value_text.len()].to_owned(),
+                raw: raw.to_owned(),
+                tail: value_text[raw.len()..].to_owned(),
+            }));
+        }
+        Ok(DotenvFile { ini: true, lines, final_newline: text.ends_with('\n') })
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &DotenvEntry> {
+        self.lines.iter().filter_map(|line| match line {
+            DotenvLine::Entry(entry) => Some(entry),
+            DotenvLine::Text(_) => None,
+        })
+    }
+
+    fn entries_mut(&mut self) -> impl Iterator<Item = &mut DotenvEntry> {
+        self.lines.iter_mut().filter_map(|line| match line {
+            DotenvLine::Entry(entry) => Some(entry),
+            DotenvLine::Text(_) => None,
+        })
+    }
+
+    pub fn lines(&self) -> &[DotenvLine] {
+        &self.lines
+    }
+
+    /// Whether the file is an INI file
+    pub fn is_ini(&self) -> bool {
+        self.ini
+    }
+
+    /// The comments of the file, with their line, column and content without the comment marker.
+    pub fn comments(&self) -> Vec<(usize, usize, &str)> {
+        let markers: &[char] = if self.ini { &[';', '#'] } else { &['#'] };
+        let mut comments = vec![];
+        let mut line = 1;
+        for text_line in &self.lines {
+            match text_line {
+                DotenvLine::Text(text) => {
+                    let content = text.trim_start();
+                    if let Some(comment) = content.strip_prefix(markers) {
+                        comments.push((line, text.len() - content.len() + 1, comment));
+                    }
+                }
+                DotenvLine::Entry(entry) => {
+                    // a quoted value may span several lines
+                    line += entry.raw.matches('\n').count();
+                    if let Some(comment) = entry.comment() {
+                        let before = format!("{}{}{}", entry.head, entry.raw, entry.tail);
+                        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                        comments.push((line, before.len() - line_start - comment.len(), comment));
+                    }
+                }
+            }
+            line += 1;
+        }
+        comments
+    }
+
+    /// Encrypt the values that are not encrypted yet.
+    pub fn encrypt(&mut self, recipients: &[x25519::Recipient]) -> Result<()> {
+        for entry in self.entries_mut() {
+            if YageEncodedValue::from_str(&entry.value).is_err() {
+                entry.value = encrypt_text(&yaml_scalar(&entry.value), recipients)?;
+                entry.raw = entry.value.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Decrypt the encrypted values.
+    pub fn decrypt(&mut self, identities: &[x25519::Identity]) -> Result<()> {
+        let ini = self.ini;
+        for entry in self.entries_mut() {
+            if YageEncodedValue::from_str(&entry.value).is_ok() {
+                let value = match decrypt_value(&entry.value, identities)? {
+                    YamlNode::Scalar(scalar) => scalar.as_string(),
+                    value => yaml_to_json(&value).to_string(),
+                };
+                entry.raw = if ini { ini_value(&value)? } else { dotenv_value(&value) };
+                entry.value = value;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the value of the entry at the path: the key, or `section.key` for an entry in a section
+    /// of an INI file. A new entry is added at the end of its section, created if needed.
+    pub fn set(&mut self, path: &str, value: &str) -> Result<()> {
+        let format = if self.ini { "ini" } else { "dotenv" };
+        let raw = if self.ini { ini_value(value)? } else { dotenv_value(value) };
+        let found = self.entries_mut().find(|entry| match &entry.section {
+            Some(section) => path == format!("{section}.{}", entry.key),
+            None => entry.key == path,
+        });
+        if let Some(entry) = found {
+            entry.value = value.to_owned();
+            entry.raw = raw;
+            return Ok(());
+        }
+        let (section, key) = match path.split_once('.') {
+            Some((section, key)) if self.ini => (Some(section.to_owned()), key),
+            _ => (None, path),
+        };
+        let valid = if self.ini {
+            !key.trim().is_empty() && !key.contains(['=', ':', '\n', '\r', '[', ';', '#'])
+        } else {
+            is_valid_key(key)
+        };
+        if !valid {
+            return Err(YageError::UnsupportedValue {
+                value: path.to_owned(),
+                format: format.into(),
+            });
+        }
+        let head = if self.ini { format!("{key} = ") } else { format!("{key}=") };
+        let entry = DotenvEntry {
+            section: section.clone(),
+            key: key.to_owned(),
+            value: value.to_owned(),
+            head,
+            raw,
+            tail: String::new(),
+        };
+        let is_header = |line: &DotenvLine, name: Option<&str>| match line {
+            DotenvLine::Text(text) => {
+                let header = text.trim().strip_prefix('[').and_then(|h| h.strip_suffix(']'));
+                header.is_some_and(|h| name.is_none_or(|name| h.trim() == name))
+            }
+            DotenvLine::Entry(_) => false,
+        };
+        let index = match &section {
+            // the entries without a section are before the first section
+            None if self.ini => {
+                self.lines.iter().position(|line| is_header(line, None)).unwrap_or(self.lines.len())
+            }
+            None => self.lines.len(),
+            Some(name) => match self.lines.iter().position(|line| is_header(line, Some(name))) {
+                Some(header) => {
+                    let in_section = |line: &DotenvLine| matches!(line, DotenvLine::Entry(e) if e.section.as_ref() == Some(name));
+                    let last = self.lines.iter().rposition(in_section).unwrap_or(header);
+                    last.max(header) + 1
+                }
+                None => {
+                    self.lines.push(DotenvLine::Text(format!("[{name}]")));
+                    self.lines.len()
+                }
+            },
+        };
+        if self.lines.is_empty() {
+            self.final_newline = true;
+        }
+        self.lines.insert(index, DotenvLine::Entry(entry));
+        Ok(())
+    }
+
+    /// Take the values that are equal in `previous` and in this file from `original`.
+    ///
+    /// `previous` and `original` are two versions of the same file, with the same entries.
+    pub fn keep_unchanged(&mut self, previous: &DotenvFile, original: &DotenvFile) {
+        let previous: Vec<_> = previous.entries().zip(original.entries()).collect();
+        for entry in self.entries_mut() {
+            let found =
+                previous.iter().find(|(p, _)| p.section == entry.section && p.key == entry.key);
+            if let Some((prev, orig)) = found
+                && prev.value == entry.value
+            {
+                entry.value = orig.value.clone();
+                entry.raw = orig.raw.clone();
+            }
+        }
+    }
+
+    pub fn encryption_status(&self) -> EncryptionStatus {
+        combine_encryption_status(self.entries().map(|entry| {
+            if YageEncodedValue::from_str(&entry.value).is_ok() {
+                EncryptionStatus::Encrypted
+            } else {
+                EncryptionStatus::NotEncrypted
+            }
+        }))
+    }
+
+    pub fn encrypted_values(&self) -> Vec<YageEncodedValue> {
+        self.entries().filter_map(|entry| YageEncodedValue::from_str(&entry.value).ok()).collect()
+    }
+
+    /// The entries as a JSON object. The INI sections are nested objects.
+    pub fn to_json(&self) -> Value {
+        let mut root = Map::new();
+        for entry in self.entries() {
+            let map = match &entry.section {
+                Some(section) => {
+                    let value =
+                        root.entry(section.clone()).or_insert_with(|| Value::Object(Map::new()));
+                    match value {
+                        Value::Object(map) => map,
+                        _ => continue,
+                    }
+                }
+                None => &mut root,
+            };
+            map.insert(entry.key.clone(), Value::String(entry.value.clone()));
+        }
+        Value::Object(root)
+    }
+}
+
+impl std::fmt::Display for DotenvFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = self
+            .lines
+            .iter()
+            .map(|line| match line {
+                DotenvLine::Text(text) => text.clone(),
+                DotenvLine::Entry(entry) => format!("{}{}{}", entry.head, entry.raw, entry.tail),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        write!(f, "{text}")?;
+        if self.final_newline && !self.lines.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+}
+
+/// Format a dotenv value: unquoted when possible, then single quoted, then double quoted.
+fn dotenv_value(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:@,+=%".contains(c)) {
+        value.to_owned()
+    } else if !value.contains(['\'', '\n', '\r']) {
+        format!("'{value}'")
+    } else {
+        dotenv_double_quoted(value)
+    }
+}
+
+/// Double quote a dotenv value, with escape sequences for the new lines. The dollar signs are
+/// escaped too, as the dotenv loaders expand the variables in the double quoted values.
+pub(crate) fn dotenv_double_quoted(value: &str) -> String {
+    let value = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{value}\"")
+}
+
+/// Format an INI value: quoted when its spaces or its quotes would be lost otherwise.
+fn ini_value(value: &str) -> Result<String> {
+    if value.contains(['\n', '\r']) {
+        return Err(YageError::UnsupportedValue { value: value.to_owned(), format: "ini".into() });
+    }
+    if value.trim() != value || value.starts_with(['"', '\'']) {
+        Ok(format!("\"{value}\""))
+    } else {
+        Ok(value.to_owned())
+    }
+}
+
+/// Find the closing quote in a quoted text. The double quotes may be escaped with a backslash.
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some('t') => output.push('\t'),
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
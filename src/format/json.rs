@@ -0,0 +1,216 @@
+use std::str::FromStr;
+
+use age::x25519;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{Result, YageError};
+use crate::{
+    EncryptionStatus, YageEncodedValue, combine_encryption_status, decrypt_value, encrypt_text,
+    yaml_scalar, yaml_to_json,
+};
+
+/// A JSON file, with the formatting needed to write it back like it was read.
+#[derive(Debug, Clone)]
+pub struct JsonFile {
+    pub value: Value,
+    /// The indentation of the file, or None for a compact file on a single line
+    indent: Option<String>,
+    final_newline: bool,
+}
+
+impl JsonFile {
+    pub fn parse(text: &str) -> Result<Self> {
+        let value = serde_json::from_str(text)?;
+        let indent = if text.trim().contains('\n') {
+            let indent = text
+                .lines()
+                .skip(1)
+                .map(|line| &line[..line.len() - line.trim_start().len()])
+                .find(|indent| !indent.is_empty())
+                .unwrap_or("  ");
+            Some(indent.to_owned())
+        } else {
+            None
+        };
+        Ok(JsonFile { value, indent, final_newline: text.ends_with('\n') })
+    }
+}
+
+impl std::fmt::Display for JsonFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match &self.indent {
+            Some(indent) => {
+                let mut buffer = Vec::new();
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+                let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+                self.value.serialize(&mut serializer).map_err(|_| std::fmt::Error)?;
+                String::from_utf8(buffer).map_err(|_| std::fmt::Error)?
+            }
+            None => self.value.to_string(),
+        };
+        write!(f, "{text}")?;
+        if self.final_newline {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encrypt the strings and the numbers of a JSON value.
+///
+/// The encrypted values are JSON strings in the `yage[…]` format. The plain text of an encrypted
+/// value is its YAML representation, so the values can be decrypted to the same type, and moved
+/// between the YAML and the JSON files.
+pub fn encrypt_json(value: &Value, recipients: &[x25519::Recipient]) -> Result<Value> {
+    Ok(match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), encrypt_json(v, recipients)?)))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| encrypt_json(v, recipients)).collect::<Result<_>>()?)
+        }
+        Value::String(s) if YageEncodedValue::from_str(s).is_ok() => value.clone(),
+        Value::String(s) => Value::String(encrypt_text(&yaml_scalar(s), recipients)?),
+        Value::Number(n) => Value::String(encrypt_text(&n.to_string(), recipients)?),
+        Value::Bool(_) | Value::Null => value.clone(),
+    })
+}
+
+/// Decrypt the encrypted values of a JSON value.
+pub fn decrypt_json(value: &Value, identities: &[x25519::Identity]) -> Result<Value> {
+    Ok(match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), decrypt_json(v, identities)?)))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| decrypt_json(v, identities)).collect::<Result<_>>()?)
+        }
+        Value::String(s) if YageEncodedValue::from_str(s).is_ok() => {
+            yaml_to_json(&decrypt_value(s, identities)?)
+        }
+        _ => value.clone(),
+    })
+}
+
+/// Select the value at the dot separated path. The array items are selected by their index.
+pub fn select_json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => key.parse().ok().and_then(|i: usize| items.get(i)),
+        _ => None,
+    })
+}
+
+fn select_json_path_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get_mut(key),
+        Value::Array(items) => key.parse().ok().and_then(|i: usize| items.get_mut(i)),
+        _ => None,
+    })
+}
+
+/// Set the value at the dot separated path. The last key is added to its object if needed.
+pub(crate) fn set_json_path(value: &mut Value, path: &str, new_value: Value) -> Result<()> {
+    let not_found = || YageError::PathNotFound { path: path.to_owned() };
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent_path, key)) => (select_json_path_mut(value, parent_path), key),
+        None => (Some(value), path),
+    };
+    match parent.ok_or_else(not_found)? {
+        Value::Object(map) => {
+            map.insert(key.to_owned(), new_value);
+        }
+        Value::Array(items) => {
+            *key.parse().ok().and_then(|i: usize| items.get_mut(i)).ok_or_else(not_found)? =
+                new_value;
+        }
+        _ => return Err(not_found()),
+    }
+    Ok(())
+}
+
+pub fn check_json_encrypted(value: &Value) -> EncryptionStatus {
+    match value {
+        Value::Object(map) => combine_encryption_status(map.values().map(check_json_encrypted)),
+        Value::Array(items) => combine_encryption_status(items.iter().map(check_json_encrypted)),
+        Value::String(s) if YageEncodedValue::from_str(s).is_ok() => EncryptionStatus::Encrypted,
+        Value::String(_) | Value::Number(_) => EncryptionStatus::NotEncrypted,
+        Value::Bool(_) | Value::Null => EncryptionStatus::NoValue,
+    }
+}
+
+pub fn flatten_json_encrypted_values(value: &Value) -> Vec<YageEncodedValue> {
+    match value {
+        Value::Object(map) => map.values().flat_map(flatten_json_encrypted_values).collect(),
+        Value::Array(items) => items.iter().flat_map(flatten_json_encrypted_values).collect(),
+        Value::String(s) => YageEncodedValue::from_str(s).into_iter().collect(),
+        _ => vec![],
+    }
+}
+
+/// Convert a JSON value to a YAML text, in block style.
+pub fn json_to_yaml(value: &Value) -> String {
+    let mut yaml = String::new();
+    write_yaml_value(&mut yaml, value, 0);
+    yaml
+}
+
+fn write_yaml_value(yaml: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                yaml.push_str(&format!("{pad}{}:", yaml_scalar(key)));
+                write_yaml_child(yaml, value, indent + 2);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                match item {
+                    Value::Object(map) if !map.is_empty() => {
+                        // the first key of the mapping is on the same line as the `-` marker
+                        let mut nested = String::new();
+                        write_yaml_value(&mut nested, item, indent + 2);
+                        yaml.push_str(&format!("{pad}- {}", &nested[indent + 2..]));
+                    }
+                    _ => {
+                        yaml.push_str(&format!("{pad}-"));
+                        write_yaml_child(yaml, item, indent + 2);
+                    }
+                }
+            }
+        }
+        _ => {
+            yaml.push_str(&format!("{pad}{}\n", json_scalar(value)));
+        }
+    }
+}
+
+/// Write a value after a `key:` or a `-` marker.
+fn write_yaml_child(yaml: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            yaml.push('\n');
+            write_yaml_value(yaml, value, indent);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            yaml.push('\n');
+            write_yaml_value(yaml, value, indent);
+        }
+        _ => yaml.push_str(&format!(" {}\n", json_scalar(value))),
+    }
+}
+
+fn json_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => yaml_scalar(s),
+        Value::Object(_) => "{}".to_owned(),
+        Value::Array(_) => "[]".to_owned(),
+        _ => value.to_string(),
+    }
+}
@@ -0,0 +1,281 @@
+use std::io::Read;
+use std::path::Path;
+
+use age::x25519;
+use clap::ValueEnum;
+use serde_json::Value;
+use strum::{Display, EnumIs, EnumString};
+use toml_edit::DocumentMut;
+use yaml_edit::{Document, YamlFile, YamlNode};
+
+use crate::error::{Result, YageError};
+use crate::format::{
+    DotenvFile, JsonFile, check_json_encrypted, check_toml_encrypted, decrypt_json, decrypt_toml,
+    encrypt_json, encrypt_toml, flatten_json_encrypted_values, flatten_toml_encrypted_values,
+    set_json_path, set_toml_path, toml_comments, toml_table_to_json,
+};
+use crate::{
+    CommentSecretPolicy, EncryptionStatus, YageEncodedValue, check_encoded_values_recipients,
+    check_encrypted, check_text_comments_for_secrets, decrypt_comments, decrypt_yaml,
+    encoded_values_recipients, encrypt_comment_secrets, encrypt_yaml,
+    flatten_yage_encrypted_values, parse_yaml_file, re_encrypt_comments, replace_document_root,
+    replace_yaml_file_document, report_comment_secrets, report_secret_leaks, set_yaml_path,
+    stdin_or_file, write_text_file, yaml_to_json,
+};
+
+/// The format of a file with encrypted values
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, EnumIs, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum FileFormat {
+    /// A YAML file
+    Yaml,
+    /// A JSON file
+    Json,
+    /// A TOML file
+    Toml,
+    /// A dotenv file, with `KEY=value` lines
+    Dotenv,
+    /// An INI file, with `key = value` lines in sections
+    Ini,
+}
+
+impl FileFormat {
+    /// The format of the file: the given format if any, or the format guessed from the file
+    /// name. YAML is used when the format can't be guessed.
+    pub fn detect(path: &Path, format: Option<FileFormat>) -> FileFormat {
+        format.or_else(|| FileFormat::from_name(path)).unwrap_or(FileFormat::Yaml)
+    }
+
+    /// The format guessed from the file name, if any: from its extension, or a name starting with
+    /// `.env` for the dotenv files.
+    pub fn from_name(path: &Path) -> Option<FileFormat> {
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Some(FileFormat::Yaml),
+            Some("json") => Some(FileFormat::Json),
+            Some("toml") => Some(FileFormat::Toml),
+            Some("env") => Some(FileFormat::Dotenv),
+            Some("ini") => Some(FileFormat::Ini),
+            _ if name.starts_with(".env") => Some(FileFormat::Dotenv),
+            _ => None,
+        }
+    }
+}
+
+/// A file with encrypted values, in one of the supported formats.
+///
+/// The values are encrypted and decrypted in place, and the file is written back with the
+/// formatting of the original file.
+pub(crate) enum SecretFile {
+    Yaml {
+        yaml_file: YamlFile,
+        doc: Document,
+        data: YamlNode,
+    },
+    Json(JsonFile),
+    Toml(DocumentMut),
+    /// A dotenv or an INI file
+    Dotenv(DotenvFile),
+}
+
+impl SecretFile {
+    pub(crate) fn parse(text: &str, format: FileFormat) -> Result<Self> {
+        Ok(match format {
+            FileFormat::Yaml => {
+                let (yaml_file, doc, data) = parse_yaml_file(text)?;
+                SecretFile::Yaml { yaml_file, doc, data }
+            }
+            FileFormat::Json => SecretFile::Json(JsonFile::parse(text)?),
+            FileFormat::Toml => SecretFile::Toml(text.parse()?),
+            FileFormat::Dotenv => SecretFile::Dotenv(DotenvFile::parse_dotenv(text)?),
+            FileFormat::Ini => SecretFile::Dotenv(DotenvFile::parse_ini(text)?),
+        })
+    }
+
+    /// Read the file, or the standard input if the path is `-`.
+    pub(crate) fn read(path: &Path, format: FileFormat) -> Result<Self> {
+        debug!("loading {format} file: {path:?}");
+        let mut text = String::new();
+        stdin_or_file(path)?.read_to_string(&mut text)?;
+        Self::parse(&text, format)
+    }
+
+    pub(crate) fn encrypted_values(&self) -> Vec<YageEncodedValue> {
+        match self {
+            SecretFile::Yaml { data, .. } => flatten_yage_encrypted_values(data),
+            SecretFile::Json(json) => flatten_json_encrypted_values(&json.value),
+            SecretFile::Toml(doc) => flatten_toml_encrypted_values(doc),
+            SecretFile::Dotenv(dotenv) => dotenv.encrypted_values(),
+        }
+    }
+
+    pub(crate) fn recipients(&self) -> Result<Vec<x25519::Recipient>> {
+        encoded_values_recipients(&self.encrypted_values())
+    }
+
+    pub(crate) fn check_recipients(&self) -> bool {
+        check_encoded_values_recipients(&self.encrypted_values())
+    }
+
+    pub(crate) fn encryption_status(&self) -> EncryptionStatus {
+        match self {
+            SecretFile::Yaml { data, .. } => check_encrypted(data),
+            SecretFile::Json(json) => check_json_encrypted(&json.value),
+            SecretFile::Toml(doc) => check_toml_encrypted(doc),
+            SecretFile::Dotenv(dotenv) => dotenv.encryption_status(),
+        }
+    }
+
+    /// Report the secrets found in the comments.
+    ///
+    /// Only the comments of the YAML files can be encrypted: with the `encrypt` policy, the
+    /// secrets found in the comments of the other formats are an error.
+    pub(crate) fn report_comment_secrets(
+        &self,
+        path: &Path,
+        policy: CommentSecretPolicy,
+    ) -> Result<()> {
+        let (leaks, format) = match self {
+            SecretFile::Yaml { yaml_file, .. } => {
+                return report_comment_secrets(path, yaml_file, policy);
+            }
+            // JSON has no comments
+            SecretFile::Json(_) => return Ok(()),
+            SecretFile::Toml(doc) => {
+                (check_text_comments_for_secrets(toml_comments(&doc.to_string())), "toml")
+            }
+            SecretFile::Dotenv(dotenv) => (
+                check_text_comments_for_secrets(dotenv.comments()),
+                if dotenv.is_ini() { "ini" } else { "dotenv" },
+            ),
+        };
+        if !policy.is_encrypt() {
+            return report_secret_leaks(path, &leaks, policy);
+        }
+        match report_secret_leaks(path, &leaks, CommentSecretPolicy::Fail) {
+            Err(YageError::CommentSecret { path }) => {
+                Err(YageError::CommentSecretNotEncrypted { path, format: format.into() })
+            }
+            result => result,
+        }
+    }
+
+    /// Encrypt the values that are not encrypted yet.
+    pub(crate) fn encrypt(&mut self, recipients: &[x25519::Recipient]) -> Result<()> {
+        match self {
+            SecretFile::Yaml { yaml_file, doc, data } => {
+                *data = encrypt_yaml(data, recipients)?;
+                replace_document_root(doc, data);
+                replace_yaml_file_document(yaml_file, doc);
+            }
+            SecretFile::Json(json) => json.value = encrypt_json(&json.value, recipients)?,
+            SecretFile::Toml(doc) => encrypt_toml(doc, recipients)?,
+            SecretFile::Dotenv(dotenv) => dotenv.encrypt(recipients)?,
+        }
+        Ok(())
+    }
+
+    /// Decrypt the values, and the encrypted comments.
+    pub(crate) fn decrypt(&mut self, identities: &[x25519::Identity]) -> Result<()> {
+        match self {
+            SecretFile::Yaml { yaml_file, doc, data } => {
+                *data = decrypt_yaml(data, identities)?;
+                replace_document_root(doc, data);
+                replace_yaml_file_document(yaml_file, doc);
+                decrypt_comments(yaml_file, identities)?;
+            }
+            SecretFile::Json(json) => json.value = decrypt_json(&json.value, identities)?,
+            SecretFile::Toml(doc) => decrypt_toml(doc, identities)?,
+            SecretFile::Dotenv(dotenv) => dotenv.decrypt(identities)?,
+        }
+        Ok(())
+    }
+
+    /// Encrypt all the values again, and the encrypted comments, with new data keys.
+    pub(crate) fn re_encrypt(
+        &mut self,
+        identities: &[x25519::Identity],
+        recipients: &[x25519::Recipient],
+    ) -> Result<()> {
+        match self {
+            SecretFile::Yaml { yaml_file, doc, data } => {
+                *data = encrypt_yaml(&decrypt_yaml(data, identities)?, recipients)?;
+                replace_document_root(doc, data);
+                replace_yaml_file_document(yaml_file, doc);
+                re_encrypt_comments(yaml_file, identities, recipients)?;
+            }
+            SecretFile::Json(json) => {
+                json.value = encrypt_json(&decrypt_json(&json.value, identities)?, recipients)?;
+            }
+            SecretFile::Toml(doc) => {
+                decrypt_toml(doc, identities)?;
+                encrypt_toml(doc, recipients)?;
+            }
+            SecretFile::Dotenv(dotenv) => {
+                dotenv.decrypt(identities)?;
+                dotenv.encrypt(recipients)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypt the high-entropy tokens found in the comments.
+    pub(crate) fn encrypt_comment_secrets(&self, recipients: &[x25519::Recipient]) -> Result<()> {
+        if let SecretFile::Yaml { yaml_file, .. } = self {
+            encrypt_comment_secrets(yaml_file, recipients)?;
+        }
+        Ok(())
+    }
+
+    /// The values of the file as JSON. The values are not decrypted.
+    pub(crate) fn to_json(&self) -> Value {
+        match self {
+            SecretFile::Yaml { data, .. } => yaml_to_json(data),
+            SecretFile::Json(json) => json.value.clone(),
+            SecretFile::Toml(doc) => toml_table_to_json(doc.as_table()),
+            SecretFile::Dotenv(dotenv) => dotenv.to_json(),
+        }
+    }
+
+    /// Set the value at the dot separated path. The value is not encrypted.
+    pub(crate) fn set(&mut self, path: &str, value: &Value) -> Result<()> {
+        match self {
+            SecretFile::Yaml { yaml_file, .. } => {
+                let text = set_yaml_path(yaml_file, path, value)?;
+                *self = SecretFile::parse(&text, FileFormat::Yaml)?;
+            }
+            SecretFile::Json(json) => set_json_path(&mut json.value, path, value.clone())?,
+            SecretFile::Toml(doc) => set_toml_path(doc, path, value)?,
+            SecretFile::Dotenv(dotenv) => {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Number(_) | Value::Bool(_) => value.to_string(),
+                    Value::Null | Value::Array(_) | Value::Object(_) => {
+                        return Err(YageError::UnsupportedValue {
+                            value: value.to_string(),
+                            format: "dotenv".into(),
+                        });
+                    }
+                };
+                dotenv.set(path, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the file to the path, or to the standard output if the path is `-`.
+    pub(crate) fn write(&self, path: &Path, backup: bool) -> Result<()> {
+        write_text_file(path, &self.to_string(), backup)
+    }
+}
+
+impl std::fmt::Display for SecretFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretFile::Yaml { yaml_file, .. } => write!(f, "{yaml_file}"),
+            SecretFile::Json(json) => write!(f, "{json}"),
+            SecretFile::Toml(doc) => write!(f, "{doc}"),
+            SecretFile::Dotenv(dotenv) => write!(f, "{dotenv}"),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::str::FromStr;
+
+use age::x25519;
+use serde_json::Value as JsonValue;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table, Value};
+use yaml_edit::{ScalarType, ScalarValue, YamlNode};
+
+use crate::error::{Result, YageError};
+use crate::{
+    EncryptionStatus, YageEncodedValue, combine_encryption_status, decrypt_value, encrypt_text,
+    yaml_scalar, yaml_to_json,
+};
+
+/// Encrypt the strings and the numbers of a TOML document.
+///
+/// The encrypted values are TOML strings in the `yage[…]` format, with the comments and the
+/// whitespace of the original values. The booleans and the dates are left in clear.
+pub fn encrypt_toml(doc: &mut DocumentMut, recipients: &[x25519::Recipient]) -> Result<()> {
+    visit_toml_values(doc.as_item_mut(), &mut |value| {
+        let plain_text = match &*value {
+            Value::String(s) if YageEncodedValue::from_str(s.value()).is_ok() => return Ok(()),
+            Value::String(s) => yaml_scalar(s.value()),
+            Value::Integer(i) => i.value().to_string(),
+            Value::Float(f) => yaml_float(*f.value()),
+            _ => return Ok(()),
+        };
+        replace_value(value, Value::from(encrypt_text(&plain_text, recipients)?));
+        Ok(())
+    })
+}
+
+/// Decrypt the encrypted values of a TOML document.
+///
+/// The decrypted collections are written as inline arrays and tables.
+pub fn decrypt_toml(doc: &mut DocumentMut, identities: &[x25519::Identity]) -> Result<()> {
+    visit_toml_values(doc.as_item_mut(), &mut |value| {
+        if let Value::String(s) = &*value
+            && YageEncodedValue::from_str(s.value()).is_ok()
+        {
+            let decrypted = decrypted_to_toml(&decrypt_value(s.value(), identities)?)?;
+            replace_value(value, decrypted);
+        }
+        Ok(())
+    })
+}
+
+pub fn check_toml_encrypted(doc: &DocumentMut) -> EncryptionStatus {
+    combine_encryption_status(toml_values(doc.as_item()).into_iter().map(|value| match value {
+        Value::String(s) if YageEncodedValue::from_str(s.value()).is_ok() => {
+            EncryptionStatus::Encrypted
+        }
+        Value::String(_) | Value::Integer(_) | Value::Float(_) => EncryptionStatus::NotEncrypted,
+        _ => EncryptionStatus::NoValue,
+    }))
+}
+
+pub fn flatten_toml_encrypted_values(doc: &DocumentMut) -> Vec<YageEncodedValue> {
+    toml_values(doc.as_item())
+        .into_iter()
+        .filter_map(|value| YageEncodedValue::from_str(value.as_str()?).ok())
+        .collect()
+}
+
+/// The comments of a TOML document, with their line, column and content without the `#`.
+pub(crate) fn toml_comments(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut comments = vec![];
+    // the delimiter of the string continued on the next line
+    let mut open: Option<&str> = None;
+    for (i, line) in text.lines().enumerate() {
+        let mut pos = 0;
+        while pos < line.len() {
+            let rest = &line[pos..];
+            if let Some(delimiter) = open {
+                match string_end(rest, delimiter) {
+                    Some(end) => {
+                        pos += end;
+                        open = None;
+                    }
+                    None => break,
+                }
+            } else if let Some(comment) = rest.strip_prefix('#') {
+                comments.push((i + 1, pos + 1, comment));
+                break;
+            } else if let Some(delimiter) =
+                [r#"""""#, "'''", "\"", "'"].into_iter().find(|d| rest.starts_with(d))
+            {
+                pos += delimiter.len();
+                open = Some(delimiter);
+            } else {
+                pos += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        // only the multi-line strings continue on the next line
+        if matches!(open, Some("\"" | "'")) {
+            open = None;
+        }
+    }
+    comments
+}
+
+/// The position after the end of a TOML string in the text following its opening delimiter.
+fn string_end(text: &str, delimiter: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' && delimiter.starts_with('"') {
+            chars.next();
+        } else if text[i..].starts_with(delimiter) {
+            let mut end = i + delimiter.len();
+            // a multi-line string may end with up to two quotes before its delimiter
+            if delimiter.len() == 3 {
+                for _ in 0..2 {
+                    if text[end..].starts_with(&delimiter[..1]) {
+                        end += 1;
+                    }
+                }
+            }
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// Convert a TOML value to a JSON value, to compare the values without their formatting. The
+/// dates are converted to strings.
+pub fn toml_value_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::String(s) => JsonValue::String(s.value().clone()),
+        Value::Integer(i) => JsonValue::from(*i.value()),
+        Value::Float(f) => JsonValue::from(*f.value()),
+        Value::Boolean(b) => JsonValue::Bool(*b.value()),
+        Value::Datetime(d) => JsonValue::String(d.value().to_string()),
+        Value::Array(a) => JsonValue::Array(a.iter().map(toml_value_to_json).collect()),
+        Value::InlineTable(t) => JsonValue::Object(
+            t.iter().map(|(k, v)| (k.to_owned(), toml_value_to_json(v))).collect(),
+        ),
+    }
+}
+
+/// Convert a TOML table to a JSON object, like `toml_value_to_json`.
+pub fn toml_table_to_json(table: &Table) -> JsonValue {
+    JsonValue::Object(
+        table
+            .iter()
+            .filter_map(|(k, item)| {
+                let value = match item {
+                    Item::Value(v) => toml_value_to_json(v),
+                    Item::Table(t) => toml_table_to_json(t),
+                    Item::ArrayOfTables(a) => {
+                        JsonValue::Array(a.iter().map(toml_table_to_json).collect())
+                    }
+                    Item::None => return None,
+                };
+                Some((k.to_owned(), value))
+            })
+            .collect(),
+    )
+}
+
+/// Replace a value, keeping the comments and the whitespace around it.
+pub(crate) fn replace_value(value: &mut Value, new_value: Value) {
+    let decor = value.decor().clone();
+    *value = new_value;
+    *value.decor_mut() = decor;
+}
+
+/// Set the value at the dot separated path. The last key is added to its table if needed.
+///
+/// The comments and the whitespace around a replaced value are kept.
+pub(crate) fn set_toml_path(
+    doc: &mut DocumentMut,
+    path: &str,
+    new_value: &JsonValue,
+) -> Result<()> {
+    let not_found = || YageError::PathNotFound { path: path.to_owned() };
+    let new_value = json_to_toml(new_value)?;
+    let keys: Vec<_> = path.split('.').collect();
+    let (last, parents) = keys.split_last().ok_or_else(not_found)?;
+    let mut item = doc.as_item_mut();
+    for key in parents {
+        item = toml_item_mut(item, key).ok_or_else(not_found)?;
+    }
+    if let Some(Item::Value(value)) = toml_item_mut(item, last) {
+        replace_value(value, new_value);
+        return Ok(());
+    }
+    match item {
+        Item::Table(table) => {
+            table.insert(last, Item::Value(new_value));
+        }
+        Item::Value(Value::InlineTable(table)) => {
+            table.insert(*last, new_value);
+        }
+        _ => return Err(not_found()),
+    }
+    Ok(())
+}
+
+/// The item for the key in a table, or for the index in an array.
+fn toml_item_mut<'a>(item: &'a mut Item, key: &str) -> Option<&'a mut Item> {
+    match key.parse::<usize>() {
+        Ok(i) if matches!(item, Item::ArrayOfTables(_) | Item::Value(Value::Array(_))) => {
+            item.get_mut(i)
+        }
+        _ => item.get_mut(key),
+    }
+}
+
+/// Call `f` on all the scalar values of the TOML item, recursively.
+fn visit_toml_values(item: &mut Item, f: &mut impl FnMut(&mut Value) -> Result<()>) -> Result<()> {
+    match item {
+        Item::Value(value) => visit_toml_value(value, f),
+        Item::Table(table) => table.iter_mut().try_for_each(|(_, item)| visit_toml_values(item, f)),
+        Item::ArrayOfTables(tables) => tables
+            .iter_mut()
+            .flat_map(|table| table.iter_mut())
+            .try_for_each(|(_, item)| visit_toml_values(item, f)),
+        Item::None => Ok(()),
+    }
+}
+
+fn visit_toml_value(value: &mut Value, f: &mut impl FnMut(&mut Value) -> Result<()>) -> Result<()> {
+    match value {
+        Value::Array(array) => array.iter_mut().try_for_each(|value| visit_toml_value(value, f)),
+        Value::InlineTable(table) => {
+            table.iter_mut().try_for_each(|(_, value)| visit_toml_value(value, f))
+        }
+        _ => f(value),
+    }
+}
+
+/// The scalar values of the TOML item, recursively.
+fn toml_values(item: &Item) -> Vec<&Value> {
+    fn collect<'a>(value: &'a Value, values: &mut Vec<&'a Value>) {
+        match value {
+            Value::Array(array) => array.iter().for_each(|value| collect(value, values)),
+            Value::InlineTable(table) => table.iter().for_each(|(_, value)| collect(value, values)),
+            _ => values.push(value),
+        }
+    }
+    match item {
+        Item::Value(value) => {
+            let mut values = vec![];
+            collect(value, &mut values);
+            values
+        }
+        Item::Table(table) => table.iter().flat_map(|(_, item)| toml_values(item)).collect(),
+        Item::ArrayOfTables(tables) => tables
+            .iter()
+            .flat_map(|table| table.iter().flat_map(|(_, item)| toml_values(item)))
+            .collect(),
+        Item::None => vec![],
+    }
+}
+
+/// The YAML representation of a float, that is always read back as a float.
+fn yaml_float(f: f64) -> String {
+    if f.is_nan() {
+        ".nan".to_owned()
+    } else if f.is_infinite() {
+        if f > 0.0 { ".inf".to_owned() } else { "-.inf".to_owned() }
+    } else {
+        format!("{f:?}")
+    }
+}
+
+/// Convert a decrypted value to a TOML value. The floats are converted directly, as the infinite
+/// and NaN floats have no JSON representation.
+fn decrypted_to_toml(value: &YamlNode) -> Result<Value> {
+    if let YamlNode::Scalar(scalar) = value {
+        let scalar_value = ScalarValue::from_scalar(scalar);
+        if matches!(scalar_value.scalar_type(), ScalarType::Float)
+            && let Ok(f) = f64::try_from(scalar)
+        {
+            return Ok(Value::from(f));
+        }
+    }
+    json_to_toml(&yaml_to_json(value))
+}
+
+/// Convert a JSON value to a TOML value.
+fn json_to_toml(value: &JsonValue) -> Result<Value> {
+    Ok(match value {
+        JsonValue::String(s) => Value::from(s.as_str()),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => Value::from(i),
+            None => Value::from(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        JsonValue::Bool(b) => Value::from(*b),
+        JsonValue::Array(items) => {
+            Value::Array(items.iter().map(json_to_toml).collect::<Result<Array>>()?)
+        }
+        JsonValue::Object(map) => Value::InlineTable(
+            map.iter()
+                .map(|(k, v)| Ok((k.as_str(), json_to_toml(v)?)))
+                .collect::<Result<InlineTable>>()?,
+        ),
+        JsonValue::Null => {
+            return Err(YageError::UnsupportedValue {
+                value: value.to_string(),
+                format: "toml".into(),
+            });
+        }
+    })
+}
@@ -1,8 +1,46 @@
+//! Encrypted values in YAML, JSON, TOML, dotenv and INI files, with age.
+//!
+//! This crate is the library behind the `yage` command line tool. A [`Document`] is a file whose
+//! values are encrypted in place: its values can be read, set, encrypted and decrypted, and the
+//! file is written back with its formatting and its comments. A [`Vault`] holds the keys used to
+//! decrypt the values, and the recipients they are encrypted to.
+//!
+//! ```no_run
+//! use yage::{Document, Vault};
+//!
+//! let vault = Vault::builder().key_file("prod.key").build()?;
+//! let doc = Document::open("secrets.yaml")?;
+//! let password = doc.get_decrypted("database.password", &vault)?;
+//! # Ok::<(), yage::error::YageError>(())
+//! ```
+//!
+//! Only the age X25519 keys are supported, like by the command line tool: the identities and the
+//! recipients of the API are the types of the [`x25519`] module, re-exported from the `age` crate.
+//! The SSH keys, the plugins and the passphrases of age can't be used.
+
 #[macro_use]
 extern crate log;
 
 pub mod cli;
+pub mod de;
+mod document;
 pub mod error;
+mod process;
+mod secret_dir;
+mod vault;
+
+mod format {
+    mod dotenv;
+    mod json;
+    mod secret_file;
+    mod toml;
+    pub use dotenv::*;
+    pub use json::*;
+    pub use secret_file::*;
+    pub use toml::*;
+}
+
+pub use format::FileFormat;
 
 pub mod cmd {
     mod check;
@@ -10,19 +48,35 @@ pub mod cmd {
     mod edit;
     mod encrypt;
     mod env;
+    mod exec;
+    mod export;
+    mod helm;
+    mod import;
+    mod k8s_secret;
     mod keygen;
+    mod krm;
     mod pubkey;
     mod re_encrypt;
     mod recipients;
+    mod render;
+    mod rotate;
     pub use check::*;
     pub use decrypt::*;
     pub use edit::*;
     pub use encrypt::*;
     pub use env::*;
+    pub use exec::*;
+    pub use export::*;
+    pub use helm::*;
+    pub use import::*;
+    pub use k8s_secret::*;
     pub use keygen::*;
+    pub use krm::*;
     pub use pubkey::*;
     pub use re_encrypt::*;
     pub use recipients::*;
+    pub use render::*;
+    pub use rotate::*;
 }
 
 use std::fs::{File, OpenOptions};
@@ -33,16 +87,22 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use age::x25519;
+pub use age::x25519;
 use base64::prelude::*;
 use flate2::read::DeflateDecoder;
 use flate2::write::DeflateEncoder;
 use rowan::ast::AstNode;
 use strum::{Display, EnumIs, EnumIter, EnumString};
 use substring::Substring;
-use yaml_edit::{Document, Mapping, ScalarValue, Sequence, YamlBuilder, YamlFile, YamlNode};
+use yaml_edit::{
+    Document as YamlDocument, Mapping, ScalarType, ScalarValue, Sequence, YamlBuilder, YamlFile,
+    YamlNode,
+};
 
 use crate::error::{IOResultExt, Result, YageError};
+pub use de::{Secret, from_str_decrypted};
+pub use document::Document;
+pub use vault::{Vault, VaultBuilder};
 
 pub fn stdout_or_file(path: &Path) -> Result<Box<dyn Write>> {
     Ok(if path == Path::new("-") {
@@ -90,11 +150,11 @@ pub fn stdin_or_private_file(path: &Path) -> Result<BufReader<Box<dyn Read>>> {
 }
 
 fn yaml_str_to_node(s: &str) -> Result<YamlNode> {
-    let doc = Document::from_str(s)?;
+    let doc = YamlDocument::from_str(s)?;
     node_from_document(&doc)
 }
 
-fn node_from_document(doc: &Document) -> Result<YamlNode> {
+fn node_from_document(doc: &YamlDocument) -> Result<YamlNode> {
     if let Some(mapping) = doc.as_mapping() {
         Ok(YamlNode::Mapping(mapping))
     } else if let Some(sequence) = doc.as_sequence() {
@@ -109,7 +169,7 @@ fn node_from_document(doc: &Document) -> Result<YamlNode> {
 /// Replace the root node in a Document while preserving document-level
 /// children (comments, directives, document markers). The new_root is the
 /// processed YAML tree to insert in place of the old root.
-pub(crate) fn replace_document_root(doc: &Document, new_root: &YamlNode) {
+pub(crate) fn replace_document_root(doc: &YamlDocument, new_root: &YamlNode) {
     use rowan::NodeOrToken;
     use yaml_edit::SyntaxKind;
 
@@ -139,22 +199,86 @@ pub(crate) fn replace_document_root(doc: &Document, new_root: &YamlNode) {
     }
 }
 
-/// Read a YAML file, preserving ROOT-level comments via YamlFile.
+/// Parse a YAML text, preserving ROOT-level comments via YamlFile.
 /// Returns the YamlFile, its first Document, and the root YamlNode.
-pub(crate) fn read_yaml_file(path: &Path) -> Result<(YamlFile, Document, YamlNode)> {
-    let mut s = String::new();
-    stdin_or_file(path)?.read_to_string(&mut s)?;
-    let yaml_file = YamlFile::from_str(&s)?;
+pub(crate) fn parse_yaml_file(s: &str) -> Result<(YamlFile, YamlDocument, YamlNode)> {
+    let yaml_file = YamlFile::from_str(s)?;
     let doc = yaml_file.document().unwrap_or_default();
     let value = node_from_document(&doc)?;
     Ok((yaml_file, doc, value))
 }
 
+/// Indent the block sequences written at the same level as their parent key.
+///
+/// yaml-edit attaches the keys that follow such a sequence of mappings to its last item, so the
+/// sequences are indented before parsing the YAML texts produced by other tools, like kustomize.
+pub(crate) fn indent_compact_sequences(text: &str) -> String {
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+    // the indentation of the compact sequences containing the current line
+    let mut sequences: Vec<usize> = vec![];
+    // the indentation of the current block scalar, if any
+    let mut block_scalar: Option<usize> = None;
+    let mut previous: Option<&str> = None;
+    let mut output = String::with_capacity(text.len());
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = indent_of(line);
+        let significant = !trimmed.is_empty() && !trimmed.starts_with('#');
+        if significant {
+            if block_scalar.is_some_and(|block| indent > block) {
+                output.push_str(&" ".repeat(2 * sequences.len()));
+                output.push_str(line);
+                output.push('\n');
+                continue;
+            }
+            block_scalar = None;
+            let item = trimmed == "-" || trimmed.starts_with("- ");
+            sequences.retain(|&seq| seq < indent || (seq == indent && item));
+            let after_key = previous.is_some_and(|p| {
+                indent_of(p) == indent
+                    && p.split(" #").next().unwrap_or(p).trim_end().ends_with(':')
+            });
+            if item && after_key && !sequences.contains(&indent) {
+                sequences.push(indent);
+            }
+            let content = trimmed.split(" #").next().unwrap_or(trimmed).trim_end();
+            if ends_with_block_scalar_header(content) {
+                block_scalar = Some(indent);
+            }
+            previous = Some(line);
+        }
+        if !trimmed.is_empty() {
+            output.push_str(&" ".repeat(2 * sequences.len()));
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    output
+}
+
+/// Whether the line ends with the header of a block scalar: `|` or `>`, optionally followed by an
+/// indentation indicator and a chomping indicator in any order, like `|-`, `>+`, `|2` or `>2-`.
+fn ends_with_block_scalar_header(content: &str) -> bool {
+    let header = content.rsplit([' ', '\t']).next().unwrap_or(content);
+    let Some(indicators) = header.strip_prefix(['|', '>']) else { return false };
+    let is_indentation = |c: char| matches!(c, '1'..='9');
+    let is_chomping = |c: char| matches!(c, '+' | '-');
+    let mut chars = indicators.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (None, _, _) => true,
+        (Some(a), None, _) => is_indentation(a) || is_chomping(a),
+        (Some(a), Some(b), None) => {
+            (is_indentation(a) && is_chomping(b)) || (is_chomping(a) && is_indentation(b))
+        }
+        _ => false,
+    }
+}
+
 /// Replace the DOCUMENT node inside a YamlFile's ROOT with an updated
 /// Document's syntax node. This preserves ROOT-level comments (children
 /// of the ROOT node that are siblings of the DOCUMENT, such as top-level
 /// comments before the first YAML key).
-pub(crate) fn replace_yaml_file_document(yaml_file: &YamlFile, doc: &Document) {
+pub(crate) fn replace_yaml_file_document(yaml_file: &YamlFile, doc: &YamlDocument) {
     use rowan::NodeOrToken;
     use yaml_edit::SyntaxKind;
 
@@ -170,19 +294,107 @@ pub(crate) fn replace_yaml_file_document(yaml_file: &YamlFile, doc: &Document) {
 }
 
 /// Write a YamlFile to the output path. Preserves ROOT-level comments.
-pub(crate) fn write_yaml_file(path: &Path, yaml_file: &YamlFile) -> Result<()> {
-    let yaml_text = yaml_file.to_string();
-    let mut output = stdout_or_file(path)?;
-    output.write_all(yaml_text.as_bytes())?;
+///
+/// The existing files are replaced atomically, and the previous version is kept with a `.bak`
+/// suffix if `backup` is true.
+pub(crate) fn write_yaml_file(path: &Path, yaml_file: &YamlFile, backup: bool) -> Result<()> {
+    write_text_file(path, &yaml_file.to_string(), backup)
+}
+
+/// Write a text to the output path, or to the standard output if the path is `-`.
+///
+/// The existing regular files are replaced atomically, like with `write_yaml_file`. The other
+/// paths, like the new files, the devices or the named pipes, are written directly.
+pub(crate) fn write_text_file(path: &Path, text: &str, backup: bool) -> Result<()> {
+    if path == Path::new("-") {
+        stdout().write_all(text.as_bytes())?;
+    } else if std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+        PendingWrite::new(path, text.as_bytes())?.commit(backup)?;
+    } else {
+        File::create(path).and_then(|mut file| file.write_all(text.as_bytes())).path_ctx(path)?;
+    }
     Ok(())
 }
 
+/// A file content written in a temporary file next to its destination, ready to atomically
+/// replace it.
+///
+/// The temporary file is removed if the PendingWrite is dropped without being committed.
+pub(crate) struct PendingWrite {
+    path: PathBuf,
+    temp_file: tempfile::NamedTempFile,
+}
+
+impl PendingWrite {
+    pub(crate) fn new(path: &Path, content: &[u8]) -> Result<Self> {
+        // write to the target of the symlinks, so the link is not replaced by a regular file
+        let path = match std::fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => path.to_owned(),
+        };
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut temp_file = tempfile::NamedTempFile::new_in(dir).path_ctx(dir)?;
+        temp_file.write_all(content).path_ctx(temp_file.path())?;
+        // keep the permissions and ownership of the replaced file
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            std::fs::set_permissions(temp_file.path(), metadata.permissions())
+                .path_ctx(temp_file.path())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                let uid = Some(metadata.uid());
+                let gid = Some(metadata.gid());
+                if let Err(e) = std::os::unix::fs::chown(temp_file.path(), uid, gid) {
+                    debug!("can't preserve the ownership of {path:?}: {e}");
+                }
+            }
+        }
+        temp_file.as_file().sync_all().path_ctx(temp_file.path())?;
+        Ok(PendingWrite { path, temp_file })
+    }
+
+    pub(crate) fn commit(self, backup: bool) -> Result<()> {
+        let path = &self.path;
+        if backup && path.exists() {
+            let mut backup_path = path.as_os_str().to_owned();
+            backup_path.push(".bak");
+            let backup_path = PathBuf::from(backup_path);
+            debug!("backing up {path:?} to {backup_path:?}");
+            if backup_path.exists() {
+                std::fs::remove_file(&backup_path).path_ctx(&backup_path)?;
+            }
+            // a hard link keeps the original file in place until it is atomically replaced
+            std::fs::hard_link(path, &backup_path)
+                .or_else(|_| std::fs::copy(path, &backup_path).map(|_| ()))
+                .path_ctx(&backup_path)?;
+        }
+        self.temp_file.persist(path).map_err(|e| e.error).path_ctx(path)?;
+        #[cfg(unix)]
+        if let Some(dir) = path.parent() {
+            File::open(dir).and_then(|d| d.sync_all()).path_ctx(dir)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct SecretLeak {
     pub line: usize,
     pub col: usize,
     pub z_score: f64,
 }
 
+/// What to do with the high-entropy tokens detected in the comments
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, EnumIs, clap::ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum CommentSecretPolicy {
+    /// Log a warning and keep the comment unchanged
+    Warn,
+    /// Abort with an error
+    Fail,
+    /// Replace the comment content with a `yage[…]` encrypted value
+    Encrypt,
+}
+
 fn offset_to_linecol(source: &str, offset: rowan::TextSize) -> (usize, usize) {
     let offset = usize::from(offset);
     let text_before = &source[..offset];
@@ -194,44 +406,175 @@ fn offset_to_linecol(source: &str, offset: rowan::TextSize) -> (usize, usize) {
     (line, col)
 }
 
-pub fn check_comments_for_secrets(yaml_file: &YamlFile) -> Vec<SecretLeak> {
+/// Return the content of a comment, without the leading `#` and the surrounding spaces.
+fn comment_content(comment_text: &str) -> &str {
+    comment_text
+        .strip_prefix("# ")
+        .or_else(|| comment_text.strip_prefix('#'))
+        .unwrap_or(comment_text)
+        .trim()
+}
+
+/// Return the z-scores of the high-entropy tokens found in a comment content.
+fn comment_content_leaks(text: &str) -> Vec<f64> {
+    if text.is_empty() || YageEncodedValue::from_str(text).is_ok() {
+        return vec![];
+    }
+    cleansh_entropy::scanner::Scanner::new(text)
+        .filter(|result| result.is_anomaly && result.token.len() >= 12)
+        .map(|result| result.z_score)
+        .collect()
+}
+
+fn comment_tokens(yaml_file: &YamlFile) -> Vec<rowan::SyntaxToken<yaml_edit::Lang>> {
     use rowan::NodeOrToken;
     use yaml_edit::SyntaxKind;
 
+    yaml_file
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|element| match element {
+            NodeOrToken::Token(token) if token.kind() == SyntaxKind::COMMENT => Some(token),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn check_comments_for_secrets(yaml_file: &YamlFile) -> Vec<SecretLeak> {
     let source = yaml_file.to_string();
     let mut leaks = Vec::new();
 
-    for element in yaml_file.syntax().descendants_with_tokens() {
-        let NodeOrToken::Token(token) = element else { continue };
-        if token.kind() != SyntaxKind::COMMENT {
-            continue;
-        }
+    for token in comment_tokens(yaml_file) {
         let offset = token.text_range().start();
         let (line, col) = offset_to_linecol(&source, offset);
-        let comment_text = token.text();
-        let text_to_scan = comment_text
-            .strip_prefix("# ")
-            .or_else(|| comment_text.strip_prefix('#'))
-            .unwrap_or(comment_text)
-            .trim();
-        if text_to_scan.is_empty() {
-            continue;
+        for z_score in comment_content_leaks(comment_content(token.text())) {
+            leaks.push(SecretLeak { line, col, z_score });
         }
-        for result in cleansh_entropy::scanner::Scanner::new(text_to_scan) {
-            if result.is_anomaly && result.token.len() >= 12 {
-                leaks.push(SecretLeak { line, col, z_score: result.z_score });
-            }
+    }
+    leaks
+}
+
+/// Report the high-entropy tokens detected in the comments according to the policy.
+///
+/// The encryption of the comments with the `encrypt` policy is done later, with
+/// `encrypt_comment_secrets`, once the recipients are known.
+pub(crate) fn report_comment_secrets(
+    path: &Path,
+    yaml_file: &YamlFile,
+    policy: CommentSecretPolicy,
+) -> Result<()> {
+    report_secret_leaks(path, &check_comments_for_secrets(yaml_file), policy)
+}
+
+/// Check the comments of a file in another format than YAML, given as their line, column and
+/// content without the comment marker.
+pub(crate) fn check_text_comments_for_secrets<'a>(
+    comments: impl IntoIterator<Item = (usize, usize, &'a str)>,
+) -> Vec<SecretLeak> {
+    let mut leaks = Vec::new();
+    for (line, col, content) in comments {
+        for z_score in comment_content_leaks(content.trim()) {
+            leaks.push(SecretLeak { line, col, z_score });
         }
     }
     leaks
 }
 
+/// Report the high-entropy tokens detected in the comments of a file according to the policy.
+pub(crate) fn report_secret_leaks(
+    path: &Path,
+    leaks: &[SecretLeak],
+    policy: CommentSecretPolicy,
+) -> Result<()> {
+    for leak in leaks {
+        let msg = format!(
+            "{}:{}:{}: high-entropy token detected (z-score: {})",
+            path.to_string_lossy(),
+            leak.line,
+            leak.col,
+            leak.z_score,
+        );
+        match policy {
+            CommentSecretPolicy::Warn => warn!("{msg}"),
+            CommentSecretPolicy::Fail => error!("{msg}"),
+            CommentSecretPolicy::Encrypt => debug!("{msg}"),
+        }
+    }
+    if policy.is_fail() && !leaks.is_empty() {
+        return Err(YageError::CommentSecret { path: path.to_owned() });
+    }
+    Ok(())
+}
+
+/// Replace the content of the comments for which `f` returns a new content.
+fn replace_comments(
+    yaml_file: &YamlFile,
+    mut f: impl FnMut(&str) -> Result<Option<String>>,
+) -> Result<usize> {
+    use rowan::Language;
+    use yaml_edit::{Lang, SyntaxKind};
+
+    let mut count = 0;
+    for token in comment_tokens(yaml_file) {
+        let Some(content) = f(comment_content(token.text()))? else { continue };
+        let Some(parent) = token.parent() else { continue };
+        let mut builder = rowan::GreenNodeBuilder::new();
+        builder.start_node(Lang::kind_to_raw(SyntaxKind::ROOT));
+        builder.token(Lang::kind_to_raw(SyntaxKind::COMMENT), &format!("# {content}"));
+        builder.finish_node();
+        let new_token = rowan::SyntaxNode::<Lang>::new_root_mut(builder.finish())
+            .first_token()
+            .expect("comment token");
+        let i = token.index();
+        parent.splice_children(i..i + 1, vec![new_token.into()]);
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Encrypt the content of the comments where a high-entropy token is detected.
+///
+/// The comment content is replaced by a `yage[…]` encrypted value. Returns the number of
+/// encrypted comments.
+pub fn encrypt_comment_secrets(
+    yaml_file: &YamlFile,
+    recipients: &[x25519::Recipient],
+) -> Result<usize> {
+    replace_comments(yaml_file, |text| {
+        if comment_content_leaks(text).is_empty() {
+            Ok(None)
+        } else {
+            encrypt_text(text, recipients).map(Some)
+        }
+    })
+}
+
+/// Decrypt the comments containing a `yage[…]` encrypted value.
+pub fn decrypt_comments(yaml_file: &YamlFile, identities: &[x25519::Identity]) -> Result<usize> {
+    replace_comments(yaml_file, |text| match YageEncodedValue::from_str(text) {
+        Ok(yev) => decrypt_text(&yev, identities).map(Some),
+        Err(_) => Ok(None),
+    })
+}
+
+/// Re-encrypt the comments containing a `yage[…]` encrypted value to new recipients.
+pub fn re_encrypt_comments(
+    yaml_file: &YamlFile,
+    identities: &[x25519::Identity],
+    recipients: &[x25519::Recipient],
+) -> Result<usize> {
+    replace_comments(yaml_file, |text| match YageEncodedValue::from_str(text) {
+        Ok(yev) => encrypt_text(&decrypt_text(&yev, identities)?, recipients).map(Some),
+        Err(_) => Ok(None),
+    })
+}
+
 /// Create a new independent mutable cursor over the same green tree.
 /// yaml-edit's Clone shares cursor state (parent/index/offset), so
 /// mutating one clone affects all. This gives you a separate cursor
 /// that can be mutated independently. The green tree is ref-counted
 /// so this is O(1) — no data copying.
-fn new_mut_cursor(value: &YamlNode) -> YamlNode {
+pub(crate) fn new_mut_cursor(value: &YamlNode) -> YamlNode {
     match value {
         YamlNode::Mapping(m) => {
             let green = m.syntax().green().into_owned();
@@ -412,26 +755,121 @@ pub fn decrypt_yaml(value: &YamlNode, identities: &[x25519::Identity]) -> Result
     }
 }
 
+/// Set the value at the dot separated path, and return the new text of the file. The last key is
+/// added to its map if needed.
+///
+/// The maps and the sequences of the new value are written in the flow style.
+pub(crate) fn set_yaml_path(
+    yaml_file: &YamlFile,
+    path: &str,
+    new_value: &serde_json::Value,
+) -> Result<String> {
+    let new_text = match new_value {
+        serde_json::Value::String(s) => yaml_scalar(s),
+        new_value => new_value.to_string(),
+    };
+    let not_found = || YageError::PathNotFound { path: path.to_owned() };
+    // a fresh tree, so that the text ranges of the nodes are the offsets in the text
+    let text = yaml_file.to_string();
+    let (yaml_file, doc, data) = parse_yaml_file(&text)?;
+    let keys: Vec<_> = path.split('.').collect();
+    let (last, parents) = keys.split_last().ok_or_else(not_found)?;
+    let parent = parents.iter().try_fold(data.clone(), |node, key| yaml_child(&node, key));
+    match parent.ok_or_else(not_found)? {
+        // yaml-edit doesn't indent the entries it adds to the nested maps, so the new entry is
+        // inserted in the text
+        YamlNode::Mapping(mapping) if mapping.get(*last).is_none() => {
+            Ok(insert_yaml_entry(&text, &mapping, &format!("{}: {new_text}", yaml_scalar(last))))
+        }
+        _ => {
+            let data =
+                set_yaml_keys(&data, &keys, yaml_str_to_node(&new_text)?).ok_or_else(not_found)?;
+            replace_document_root(&doc, &data);
+            replace_yaml_file_document(&yaml_file, &doc);
+            Ok(yaml_file.to_string())
+        }
+    }
+}
+
+fn yaml_child(value: &YamlNode, key: &str) -> Option<YamlNode> {
+    match value {
+        YamlNode::Mapping(mapping) => mapping.get(key),
+        YamlNode::Sequence(sequence) => sequence.get(key.parse().ok()?),
+        _ => None,
+    }
+}
+
+/// Insert the entry after the last entry of the map, with the same indentation as its entries.
+fn insert_yaml_entry(text: &str, mapping: &Mapping, entry: &str) -> String {
+    use yaml_edit::SyntaxKind;
+
+    let range = mapping.syntax().text_range();
+    let (start, end) = (usize::from(range.start()), usize::from(range.end()));
+    let entries: Vec<_> =
+        mapping.syntax().children().filter(|n| n.kind() == SyntaxKind::MAPPING_ENTRY).collect();
+    let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+        // an empty map is written in the flow style
+        let close = text[..end].rfind('}').unwrap_or(end);
+        return format!("{}{{{entry}}}{}", &text[..start], &text[close + 1..]);
+    };
+    if text[start..].starts_with('{') {
+        let close = text[..end].rfind('}').unwrap_or(end);
+        return format!("{}, {entry}{}", text[..close].trim_end(), &text[close..]);
+    }
+    let first_start = usize::from(first.text_range().start());
+    let line_start = text[..first_start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = " ".repeat(text[line_start..first_start].chars().count());
+    let last_end = usize::from(last.text_range().end());
+    let (before, after) = text.split_at(last_end);
+    if before.ends_with('\n') {
+        format!("{before}{indent}{entry}\n{after}")
+    } else {
+        format!("{before}\n{indent}{entry}{after}")
+    }
+}
+
+fn set_yaml_keys(value: &YamlNode, keys: &[&str], new_value: YamlNode) -> Option<YamlNode> {
+    let Some((key, keys)) = keys.split_first() else { return Some(new_value) };
+    match value {
+        YamlNode::Mapping(mapping) => {
+            let child = set_yaml_keys(&mapping.get(*key)?, keys, new_value)?;
+            let output = new_mut_cursor(value);
+            map_set(output.as_mapping().unwrap(), yaml_str_to_node(&yaml_scalar(key)).ok()?, child);
+            Some(output)
+        }
+        YamlNode::Sequence(sequence) => {
+            let i = key.parse().ok()?;
+            let child = set_yaml_keys(&sequence.get(i)?, keys, new_value)?;
+            let output = new_mut_cursor(value);
+            seq_set(output.as_sequence().unwrap(), i, child);
+            Some(output)
+        }
+        _ => None,
+    }
+}
+
 pub fn decrypt_value(s: &str, identities: &[x25519::Identity]) -> Result<YamlNode> {
     match YageEncodedValue::from_str(s) {
-        Ok(yev) => {
-            // raw value -> decoded value -> decrypted value -> decompressed value -> deserialized value
-            let decoded = BASE64_STANDARD.decode(yev.data)?;
-            let decryptor = age::Decryptor::new(&decoded[..])?;
-            if decryptor.is_scrypt() {
-                return Err(YageError::PassphraseUnsupported);
-            }
-            let decryptor =
-                decryptor.decrypt(identities.iter().map(|i| i as &dyn age::Identity))?;
-            let mut decompressor = DeflateDecoder::new(decryptor);
-            let mut yaml_text = String::new();
-            decompressor.read_to_string(&mut yaml_text)?;
-            yaml_str_to_node(&yaml_text)
-        }
+        // decrypted value -> deserialized value
+        Ok(yev) => yaml_str_to_node(&decrypt_text(&yev, identities)?),
         Err(_) => yaml_str_to_node(s),
     }
 }
 
+fn decrypt_text(yev: &YageEncodedValue, identities: &[x25519::Identity]) -> Result<String> {
+    // raw value -> decoded value -> decrypted value -> decompressed value
+    let decoded = BASE64_STANDARD.decode(&yev.data)?;
+    let decryptor = age::Decryptor::new(&decoded[..])?;
+    if decryptor.is_scrypt() {
+        return Err(YageError::PassphraseUnsupported);
+    }
+    let decryptor = decryptor.decrypt(identities.iter().map(|i| i as &dyn age::Identity))?;
+    let mut decompressor = DeflateDecoder::new(decryptor);
+    let mut text = String::new();
+    decompressor.read_to_string(&mut text)?;
+    Ok(text)
+}
+
 pub fn load_identities(keys: &[String], key_files: &[PathBuf]) -> Result<Vec<x25519::Identity>> {
     let mut identities: Vec<x25519::Identity> = Vec::new();
     for key in keys.iter() {
@@ -501,8 +939,12 @@ pub fn encrypt_yaml(value: &YamlNode, recipients: &[x25519::Recipient]) -> Resul
 }
 
 pub fn encrypt_value(value: &YamlNode, recipients: &[x25519::Recipient]) -> Result<String> {
-    // yaml value -> serialized value -> compressed value -> encrypted value -> encoded value
-    let yaml_text = format!("{}", value);
+    // yaml value -> serialized value -> encrypted value
+    encrypt_text(&format!("{}", value), recipients)
+}
+
+pub(crate) fn encrypt_text(text: &str, recipients: &[x25519::Recipient]) -> Result<String> {
+    // text -> compressed value -> encrypted value -> encoded value
     let mut encrypted = vec![];
     let mut encryptor = match age::Encryptor::with_recipients(
         recipients.iter().map(|r| r as &dyn age::Recipient),
@@ -512,7 +954,7 @@ pub fn encrypt_value(value: &YamlNode, recipients: &[x25519::Recipient]) -> Resu
     }
     .wrap_output(&mut encrypted)?;
     let mut compressor = DeflateEncoder::new(&mut encryptor, flate2::Compression::new(6));
-    compressor.write_all(yaml_text.as_bytes())?;
+    compressor.write_all(text.as_bytes())?;
     compressor.finish()?;
     encryptor.finish()?;
     // prepare the recipients list (sorted and deduplicated)
@@ -579,9 +1021,16 @@ pub fn check_encrypted(value: &YamlNode) -> EncryptionStatus {
 }
 
 fn check_encrypted_iter(iter: impl Iterator<Item = YamlNode>) -> EncryptionStatus {
+    combine_encryption_status(iter.map(|value| check_encrypted(&value)))
+}
+
+/// The encryption status of a collection, from the encryption status of its values.
+pub(crate) fn combine_encryption_status(
+    statuses: impl Iterator<Item = EncryptionStatus>,
+) -> EncryptionStatus {
     let mut status = EncryptionStatus::NoValue;
-    for value in iter {
-        match check_encrypted(&value) {
+    for value_status in statuses {
+        match value_status {
             EncryptionStatus::Encrypted => {
                 status = match status {
                     EncryptionStatus::Encrypted => EncryptionStatus::Encrypted,
@@ -624,8 +1073,12 @@ pub fn flatten_yage_encrypted_values(value: &YamlNode) -> Vec<YageEncodedValue>
 }
 
 pub fn check_recipients(value: &YamlNode) -> bool {
-    flatten_yage_encrypted_values(value)
-        .iter()
+    check_encoded_values_recipients(&flatten_yage_encrypted_values(value))
+}
+
+/// Whether all the encrypted values have the same recipients.
+pub(crate) fn check_encoded_values_recipients(yevs: &[YageEncodedValue]) -> bool {
+    yevs.iter()
         .filter(|v| !v.recipients.is_empty())
         .map(|v| &v.recipients)
         .collect::<Vec<_>>()
@@ -670,7 +1123,13 @@ impl std::fmt::Display for YageEncodedValue {
 }
 
 pub fn get_yaml_recipients(value: &YamlNode) -> Result<Vec<x25519::Recipient>> {
-    let yevs = flatten_yage_encrypted_values(value);
+    encoded_values_recipients(&flatten_yage_encrypted_values(value))
+}
+
+/// The recipients of all the encrypted values, sorted and deduplicated.
+pub(crate) fn encoded_values_recipients(
+    yevs: &[YageEncodedValue],
+) -> Result<Vec<x25519::Recipient>> {
     let mut recipients: Vec<_> = yevs.iter().flat_map(|yev| &yev.recipients).collect();
     recipients.sort();
     recipients.dedup();
@@ -703,3 +1162,67 @@ pub fn write_yaml(path: &Path, value: &YamlNode) -> Result<()> {
     output.write_all(yaml_text.as_bytes())?;
     Ok(())
 }
+
+/// Format a string as a YAML scalar: plain when it is read back as the same string, and double
+/// quoted otherwise.
+pub(crate) fn yaml_scalar(s: &str) -> String {
+    let plain = s.chars().all(|c| c.is_alphanumeric() || " _-./@+=,()".contains(c))
+        && !s.starts_with([' ', '-', '@'])
+        && !s.ends_with(' ')
+        && !matches!(
+            ScalarValue::auto_detect_type(s),
+            ScalarType::Null | ScalarType::Boolean | ScalarType::Integer | ScalarType::Float
+        );
+    if plain { s.to_owned() } else { serde_json::Value::String(s.to_owned()).to_string() }
+}
+
+/// Convert a YAML value to a JSON value.
+///
+/// The scalars keep their type when it has a JSON equivalent, and are converted to strings
+/// otherwise. The mapping keys are converted to strings. The aliases and the tagged values are
+/// kept as their YAML text.
+pub fn yaml_to_json(value: &YamlNode) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        YamlNode::Scalar(scalar) => {
+            let scalar_value = ScalarValue::from_scalar(scalar);
+            match scalar_value.scalar_type() {
+                ScalarType::Null => Some(Value::Null),
+                ScalarType::Boolean => scalar_value.to_bool().map(Value::Bool),
+                ScalarType::Integer => scalar_value.to_i64().map(Value::from),
+                // the integers out of the range of yaml-edit, like the ones above i64::MAX, are
+                // detected as floats
+                ScalarType::Float => {
+                    let text = scalar_value.value();
+                    text.parse::<u64>()
+                        .map(Value::from)
+                        .or_else(|_| text.parse::<i64>().map(Value::from))
+                        .ok()
+                        .or_else(|| {
+                            let f = scalar_value.to_f64()?;
+                            serde_json::Number::from_f64(f).map(Value::Number)
+                        })
+                }
+                _ => None,
+            }
+            .unwrap_or_else(|| Value::String(scalar.as_string()))
+        }
+        YamlNode::Mapping(mapping) => Value::Object(
+            mapping
+                .iter()
+                .map(|(k, v)| {
+                    let key = match &k {
+                        YamlNode::Scalar(scalar) => scalar.as_string(),
+                        _ => k.to_string(),
+                    };
+                    (key, yaml_to_json(&v))
+                })
+                .collect(),
+        ),
+        YamlNode::Sequence(sequence) => {
+            Value::Array(sequence.values().map(|v| yaml_to_json(&v)).collect())
+        }
+        YamlNode::Alias(_) | YamlNode::TaggedNode(_) => Value::String(value.to_string()),
+    }
+}
@@ -28,15 +28,24 @@ fn run() -> error::Result<i32> {
         cli::Commands::Encrypt(args) => cmd::encrypt(args),
         cli::Commands::Decrypt(args) => cmd::decrypt(args),
         cli::Commands::Env(args) => cmd::env(args),
+        cli::Commands::Exec(args) => cmd::exec(args),
+        cli::Commands::Export(args) => cmd::export(args),
+        cli::Commands::Helm(args) => cmd::helm(args),
+        cli::Commands::K8sSecret(args) => cmd::k8s_secret(args),
+        cli::Commands::Krm(args) => cmd::krm(args),
+        cli::Commands::Import(args) => cmd::import(args),
         cli::Commands::Check(args) => cmd::check(args),
         cli::Commands::Recipients(args) => cmd::recipients(args),
+        cli::Commands::Render(args) => cmd::render(args),
         cli::Commands::ReEncrypt(args) => cmd::re_encrypt(args),
+        cli::Commands::Rotate(args) => cmd::rotate(args),
     }
 }
 
 fn main() {
     match run() {
         Ok(exit_code) => std::process::exit(exit_code),
+        Err(error::YageError::Usage(err)) => err.exit(),
         Err(err) => {
             error!("{err}");
             std::process::exit(1);
@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::{IOResultExt, Result};
+
+/// The signals forwarded to the command.
+#[cfg(unix)]
+const FORWARDED_SIGNALS: [libc::c_int; 7] = [
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGWINCH,
+];
+
+/// The signals asking the command to terminate.
+#[cfg(unix)]
+const TERMINATING_SIGNALS: [libc::c_int; 4] =
+    [libc::SIGHUP, libc::SIGINT, libc::SIGQUIT, libc::SIGTERM];
+
+/// The process the signals are forwarded to, or 0.
+#[cfg(unix)]
+static CHILD_PID: AtomicU32 = AtomicU32::new(0);
+
+/// The last terminating signal received, or 0.
+#[cfg(unix)]
+static TERMINATION: AtomicI32 = AtomicI32::new(0);
+
+/// The terminating signal received while no command was running, to send to the next one, or 0.
+#[cfg(unix)]
+static PENDING_TERMINATION: AtomicI32 = AtomicI32::new(0);
+
+/// How long a stopped command may take to exit before it is killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run the command as a child process, and return its exit code.
+///
+/// On Unix, the signals received while the command runs are forwarded to it, and the exit code is
+/// 128 plus the signal number when the command is killed by a signal, like in a shell.
+pub(crate) fn run_command(command: &mut Command) -> Result<i32> {
+    let mut child = spawn_command(command)?;
+    let status = child.wait()?;
+    forget_command(&child);
+    Ok(exit_code(status))
+}
+
+/// Spawn the command as a child process, with the signals forwarded to it on Unix.
+pub(crate) fn spawn_command(command: &mut Command) -> Result<Child> {
+    let program = PathBuf::from(command.get_program());
+    // the signals are blocked before spawning the command, so that none is missed, and unblocked
+    // in the command
+    #[cfg(unix)]
+    let signals = block_signals();
+    #[cfg(unix)]
+    unsafe {
+        use std::os::unix::process::CommandExt;
+        command.pre_exec(move || {
+            libc::sigprocmask(libc::SIG_UNBLOCK, &signals, std::ptr::null_mut());
+            Ok(())
+        });
+    }
+    let child = command.spawn().path_ctx(program)?;
+    #[cfg(unix)]
+    {
+        static FORWARDING: std::sync::Once = std::sync::Once::new();
+        CHILD_PID.store(child.id(), Ordering::SeqCst);
+        FORWARDING.call_once(|| forward_signals(signals));
+        send_pending_termination();
+    }
+    Ok(child)
+}
+
+/// The terminating signal, like SIGTERM, received since the first command was started, if any.
+///
+/// A command stopped to be restarted must not be started again once a terminating signal has been
+/// received, as the signal may have been received by the stopped command only.
+pub(crate) fn termination_signal() -> Option<i32> {
+    #[cfg(unix)]
+    if let signal @ 1.. = TERMINATION.load(Ordering::SeqCst) {
+        return Some(signal);
+    }
+    None
+}
+
+/// Stop the command: terminate it gracefully, and kill it if it is still running after a while.
+pub(crate) fn stop_command(child: &mut Child) -> Result<ExitStatus> {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    #[cfg(not(unix))]
+    child.kill()?;
+    let start = Instant::now();
+    while start.elapsed() < STOP_TIMEOUT {
+        if let Some(status) = child.try_wait()? {
+            forget_command(child);
+            return Ok(status);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    warn!("the command is still running after {STOP_TIMEOUT:?} — killing it");
+    child.kill()?;
+    let status = child.wait()?;
+    forget_command(child);
+    Ok(status)
+}
+
+/// Stop forwarding the signals to the command once it has exited, so that they are not sent to
+/// another process reusing its pid.
+fn forget_command(child: &Child) {
+    #[cfg(unix)]
+    let _ = CHILD_PID.compare_exchange(child.id(), 0, Ordering::SeqCst, Ordering::SeqCst);
+    #[cfg(not(unix))]
+    let _ = child;
+}
+
+/// The exit code of a command, or 128 plus the signal number when it was killed by a signal.
+pub(crate) fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Block the forwarded signals in the current thread, and in the threads it starts.
+#[cfg(unix)]
+fn block_signals() -> libc::sigset_t {
+    unsafe {
+        let mut signals: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        for signal in FORWARDED_SIGNALS {
+            libc::sigaddset(&mut signals, signal);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut());
+        signals
+    }
+}
+
+/// Forward the blocked signals to the current child process, from a background thread.
+#[cfg(unix)]
+fn forward_signals(signals: libc::sigset_t) {
+    std::thread::spawn(move || {
+        loop {
+            let Some((signal, forward)) = wait_signal(&signals) else { continue };
+            let terminating = TERMINATING_SIGNALS.contains(&signal);
+            if terminating {
+                TERMINATION.store(signal, Ordering::SeqCst);
+            }
+            let pid = CHILD_PID.load(Ordering::SeqCst);
+            if pid == 0 && terminating {
+                // no command is running, while it is restarted: the next one is terminated
+                PENDING_TERMINATION.store(signal, Ordering::SeqCst);
+                send_pending_termination();
+            } else if pid != 0 && forward {
+                debug!("forwarding signal {signal} to process {pid}");
+                unsafe { libc::kill(pid as libc::pid_t, signal) };
+            }
+        }
+    });
+}
+
+/// Send the terminating signal received while no command was running to the current command,
+/// if any.
+#[cfg(unix)]
+fn send_pending_termination() {
+    let pid = CHILD_PID.load(Ordering::SeqCst);
+    if pid != 0 {
+        // the signal is only sent once, by the forwarding thread or by the spawning thread
+        if let signal @ 1.. = PENDING_TERMINATION.swap(0, Ordering::SeqCst) {
+            debug!("forwarding signal {signal} to process {pid}");
+            unsafe { libc::kill(pid as libc::pid_t, signal) };
+        }
+    }
+}
+
+/// Wait for one of the blocked signals, and return it with whether it must be forwarded: it is not
+/// when the child process has already received it.
+///
+/// The signals sent by the terminal, like SIGINT on Ctrl-C, are received by all the processes of
+/// its foreground process group, and the signals sent by the child process itself are not sent
+/// back to it.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn wait_signal(signals: &libc::sigset_t) -> Option<(libc::c_int, bool)> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let signal = unsafe { libc::sigwaitinfo(signals, &mut info) };
+    if signal < 0 {
+        return None;
+    }
+    let pid = CHILD_PID.load(Ordering::SeqCst) as libc::pid_t;
+    let from_terminal =
+        info.si_code == libc::SI_KERNEL && unsafe { libc::getpgid(pid) == libc::getpgrp() };
+    let from_child = info.si_code == libc::SI_USER && unsafe { info.si_pid() } == pid;
+    if pid != 0 && (from_terminal || from_child) {
+        debug!("not forwarding signal {signal}, already received by process {pid}");
+        return Some((signal, false));
+    }
+    Some((signal, true))
+}
+
+/// Wait for one of the blocked signals. The origin of the signals isn't known on this platform,
+/// so they are all forwarded.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn wait_signal(signals: &libc::sigset_t) -> Option<(libc::c_int, bool)> {
+    let mut signal = 0;
+    (unsafe { libc::sigwait(signals, &mut signal) } == 0).then_some((signal, true))
+}
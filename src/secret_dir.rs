@@ -0,0 +1,106 @@
+use std::fs;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+use crate::error::{IOResultExt, Result};
+
+/// A private temporary directory for the decrypted files.
+///
+/// The content of the files in the directory is overwritten before they are deleted.
+pub(crate) struct SecretDir(Option<TempDir>);
+
+impl SecretDir {
+    /// Create the directory in `tmpdir`, or in a memory-backed location if available.
+    pub(crate) fn new(tmpdir: Option<&Path>) -> Result<Self> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix("yage-");
+        let dir = match tmpdir {
+            Some(tmpdir) => builder.tempdir_in(tmpdir).path_ctx(tmpdir)?,
+            None => match memory_backed_dirs().find_map(|d| builder.tempdir_in(d).ok()) {
+                Some(dir) => dir,
+                None => {
+                    warn!(
+                        "no memory-backed temporary directory available — the decrypted files may be written to a disk"
+                    );
+                    builder.tempdir()?
+                }
+            },
+        };
+        debug!("temporary directory: {:?}", dir.path());
+        #[cfg(unix)]
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        Ok(SecretDir(Some(dir)))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.0.as_ref().expect("directory already kept").path()
+    }
+
+    /// Keep the directory and its content, and return its path.
+    pub(crate) fn keep(&mut self) -> PathBuf {
+        self.0.take().expect("directory already kept").keep()
+    }
+}
+
+impl Drop for SecretDir {
+    fn drop(&mut self) {
+        let Some(dir) = self.0.take() else { return };
+        // the editor may have created some other files, like swap or backup files
+        for entry in fs::read_dir(dir.path()).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_file())
+                && let Err(e) = overwrite_file(&path)
+            {
+                warn!("{}: can't overwrite the file: {e}", path.to_string_lossy());
+            }
+        }
+    }
+}
+
+/// Overwrite the content of the file with zeros.
+fn overwrite_file(path: &Path) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let zeros = [0u8; 4096];
+    while remaining > 0 {
+        let len = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..len])?;
+        remaining -= len as u64;
+    }
+    file.sync_all()
+}
+
+/// The memory-backed directories that may be used to store the decrypted file.
+fn memory_backed_dirs() -> impl Iterator<Item = PathBuf> {
+    let candidates =
+        [std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from), Some("/dev/shm".into())];
+    candidates.into_iter().flatten().filter(|d| d.is_dir() && is_memory_backed(d))
+}
+
+/// Whether the directory is on a tmpfs or ramfs filesystem, according to /proc/self/mounts.
+#[cfg(target_os = "linux")]
+fn is_memory_backed(dir: &Path) -> bool {
+    let Ok(dir) = dir.canonicalize() else { return false };
+    let Ok(mounts) = fs::read_to_string("/proc/self/mounts") else { return false };
+    // the last mount wins when several filesystems are mounted on the same mount point
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            Some((PathBuf::from(mount_point), fs_type))
+        })
+        .filter(|(mount_point, _)| dir.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .is_some_and(|(_, fs_type)| fs_type == "tmpfs" || fs_type == "ramfs")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_memory_backed(_dir: &Path) -> bool {
+    false
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use age::x25519;
+
+use crate::error::Result;
+use crate::{load_identities, load_recipients};
+
+/// The keys used to decrypt and encrypt the documents
+///
+/// A vault is created with [`Vault::builder`]. When no recipient is given, the values are
+/// encrypted to the recipients of the document, or to the public keys of the identities for a
+/// document without encrypted values.
+///
+/// The keys are age X25519 keys only.
+#[derive(Clone, Default)]
+pub struct Vault {
+    identities: Vec<x25519::Identity>,
+    recipients: Vec<x25519::Recipient>,
+}
+
+impl Vault {
+    pub fn builder() -> VaultBuilder {
+        VaultBuilder::default()
+    }
+
+    /// The private keys, used to decrypt the values.
+    pub fn identities(&self) -> &[x25519::Identity] {
+        &self.identities
+    }
+
+    /// The public keys, used to encrypt the values.
+    pub fn recipients(&self) -> &[x25519::Recipient] {
+        &self.recipients
+    }
+}
+
+impl std::fmt::Debug for Vault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the private keys are never printed
+        f.debug_struct("Vault")
+            .field("identities", &self.identities.len())
+            .field("recipients", &self.recipients.iter().map(|r| r.to_string()).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// A builder for a [`Vault`]
+///
+/// The keys and the recipients may be given as values, as strings or as files. The files are read
+/// when the vault is built.
+#[derive(Default)]
+pub struct VaultBuilder {
+    identities: Vec<x25519::Identity>,
+    keys: Vec<String>,
+    key_files: Vec<PathBuf>,
+    recipients: Vec<x25519::Recipient>,
+    recipient_strings: Vec<String>,
+    recipient_files: Vec<PathBuf>,
+}
+
+impl VaultBuilder {
+    /// Decrypt with the identity.
+    pub fn identity(mut self, identity: x25519::Identity) -> Self {
+        self.identities.push(identity);
+        self
+    }
+
+    /// Decrypt with the private key, like `AGE-SECRET-KEY-1…`.
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Decrypt with the private keys in the file, one per line.
+    pub fn key_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.key_files.push(path.into());
+        self
+    }
+
+    /// Encrypt to the recipient.
+    pub fn recipient(mut self, recipient: x25519::Recipient) -> Self {
+        self.recipients.push(recipient);
+        self
+    }
+
+    /// Encrypt to the public key, like `age1…`.
+    pub fn recipient_str(mut self, recipient: impl Into<String>) -> Self {
+        self.recipient_strings.push(recipient.into());
+        self
+    }
+
+    /// Encrypt to the public keys in the file, one per line.
+    pub fn recipient_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.recipient_files.push(path.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Vault> {
+        let mut identities = self.identities;
+        identities.extend(load_identities(&self.keys, &self.key_files)?);
+        let mut recipients = self.recipients;
+        recipients.extend(load_recipients(&self.recipient_strings, &self.recipient_files)?);
+        recipients.sort_by_cached_key(|r| r.to_string());
+        recipients.dedup();
+        Ok(Vault { identities, recipients })
+    }
+}
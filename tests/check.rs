@@ -3,6 +3,7 @@ mod common;
 use crate::common::*;
 use assert_fs::prelude::*;
 use predicates::prelude::predicate::str::*;
+use predicates::prelude::*;
 // use pretty_assertions::{assert_eq, assert_ne};
 use std::{fs::OpenOptions, io::Write};
 
@@ -18,6 +19,27 @@ fn check_emits_warning_on_high_entropy_comment() {
         .stderr(contains("high-entropy token detected"));
 }
 
+#[test]
+fn check_fails_on_high_entropy_comment() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT);
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &encrypted_path, "-qq");
+    yage!("check", &encrypted_path, "-qq").stdout(is_empty()).stderr(is_empty());
+    yage_cmd!("check", "--on-comment-secret", "fail", &encrypted_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(starts_with("error: ").and(contains("high-entropy token detected")));
+    yage_cmd!("check", "--on-comment-secret", "encrypt", &encrypted_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("invalid value 'encrypt'"));
+}
+
 #[test]
 fn check_clear() {
     let tmp = temp_dir();
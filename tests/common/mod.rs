@@ -115,6 +115,12 @@ impl ToPath for PathBuf {
     }
 }
 
+impl ToPath for TempDir {
+    fn path(&self) -> &Path {
+        self.path()
+    }
+}
+
 impl ToPath for ChildPath {
     fn path(&self) -> &Path {
         self.path()
@@ -129,6 +135,16 @@ pub fn write(path: &dyn ToPath, content: &str) {
     File::create(path.path()).unwrap().write_all(content.as_bytes()).unwrap();
 }
 
+/// Assert that no temporary file is left in the directory.
+pub fn assert_no_temp_files(dir: &dyn ToPath) {
+    let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "files left in {:?}: {leftovers:?}", dir.path());
+}
+
 pub fn temp_dir() -> TempDir {
     TempDir::new().unwrap()
 }
@@ -206,10 +222,16 @@ pub fn parse_yaml(s: &str) -> YamlNode {
 }
 
 pub fn generate_encrypted_file() -> (TempDir, PathBuf, PathBuf, PathBuf, PathBuf) {
+    generate_encrypted_file_with(YAML_CONTENT)
+}
+
+pub fn generate_encrypted_file_with(
+    content: &str,
+) -> (TempDir, PathBuf, PathBuf, PathBuf, PathBuf) {
     let tmp = temp_dir();
     let (key_path, pub_path) = create_key(&tmp);
     let yaml_path = tmp.child("file.yaml");
-    write(&yaml_path, YAML_CONTENT);
+    write(&yaml_path, content);
     let encrypted_path = tmp.child("file.enc.yaml");
     yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &encrypted_path)
         .stdout(is_empty())
@@ -0,0 +1,183 @@
+mod common;
+
+use crate::common::*;
+use assert_fs::prelude::*;
+use predicates::prelude::predicate::str::*;
+use predicates::prelude::*;
+
+const DOTENV_CONTENT: &str = r#"# database settings
+DB_HOST=localhost
+export DB_PASSWORD='p@ss word' # the password
+GREETING="hello
+world"
+"#;
+
+const DOTENV_CONTENT_ENCRYPTED_PATTERN: &str = r"^# database settings
+DB_HOST=yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]
+export DB_PASSWORD=yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\] # the password
+GREETING=yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]
+$";
+
+// the multi-line value is written back on a single line, with escape sequences
+const DOTENV_CONTENT_DECRYPTED: &str = r#"# database settings
+DB_HOST=localhost
+export DB_PASSWORD='p@ss word' # the password
+GREETING="hello\nworld"
+"#;
+
+const INI_CONTENT: &str = "; global settings
+name = app
+
+[database]
+host = localhost
+password = \" secret \"
+";
+
+const INI_CONTENT_ENCRYPTED_PATTERN: &str = r"^; global settings
+name = yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]
+
+\[database\]
+host = yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]
+password = yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]
+$";
+
+#[test]
+fn dotenv_encrypt_decrypt_in_place() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let env_path = tmp.child("app.env");
+    write(&env_path, DOTENV_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &env_path).stdout(is_empty()).stderr(is_empty());
+    read(&env_path).assert(is_match(DOTENV_CONTENT_ENCRYPTED_PATTERN).unwrap());
+    yage!("check", &env_path).stdout(is_empty()).stderr(is_empty());
+    yage!("decrypt", "-K", &key_path, "-i", &env_path).stdout(is_empty()).stderr(is_empty());
+    assert_eq!(read(&env_path), DOTENV_CONTENT_DECRYPTED);
+}
+
+#[test]
+fn dotenv_detected_from_file_name() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let env_path = tmp.child(".env.production");
+    write(&env_path, "TOKEN=abc\n");
+    yage!("encrypt", "-R", &pub_path, &env_path)
+        .stdout(is_match(r"^TOKEN=yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]\n$").unwrap())
+        .stderr(is_empty());
+}
+
+#[test]
+fn dotenv_check_not_encrypted() {
+    let tmp = temp_dir();
+    let env_path = tmp.child("app.env");
+    write(&env_path, DOTENV_CONTENT);
+    yage_cmd!("check", &env_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("not encrypted"));
+}
+
+#[test]
+fn dotenv_env() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let env_path = tmp.child("app.env");
+    write(&env_path, DOTENV_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &env_path);
+    yage!("env", "-K", &key_path, &env_path, "env")
+        .stdout(contains("DB_HOST=localhost\n").and(contains("DB_PASSWORD=p@ss word\n")))
+        .stderr(is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn dotenv_decrypt_keeps_escaped_dollar() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let env_path = tmp.child("app.env");
+    let content = "A=\"it's \\$HOME\"\n";
+    write(&env_path, content);
+    yage!("encrypt", "-R", &pub_path, "-i", &env_path);
+    yage!("decrypt", "-K", &key_path, "-i", &env_path);
+    assert_eq!(read(&env_path), content);
+    yage!("encrypt", "-R", &pub_path, "-i", &env_path);
+    yage!("env", "-K", &key_path, &env_path, "--", "sh", "-c", "echo \"$A\"")
+        .stdout("it's $HOME\n")
+        .stderr(is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn dotenv_edit_keeps_unchanged_values() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let env_path = tmp.child("app.env");
+    write(&env_path, DOTENV_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &env_path);
+    let encrypted = read(&env_path);
+    let editor = "sed -i s/localhost/db.local/";
+    yage!("edit", "-K", &key_path, "-e", editor, &env_path).stdout(is_empty()).stderr(is_empty());
+    let edited = read(&env_path);
+    let changed: Vec<_> =
+        encrypted.lines().zip(edited.lines()).filter(|(a, b)| a != b).map(|(a, _)| a).collect();
+    assert_eq!(changed.len(), 1);
+    assert!(changed[0].starts_with("DB_HOST="));
+    yage!("decrypt", "-K", &key_path, &env_path)
+        .stdout(DOTENV_CONTENT_DECRYPTED.replace("localhost", "db.local"));
+}
+
+#[test]
+fn ini_encrypt_decrypt_in_place() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let ini_path = tmp.child("app.ini");
+    write(&ini_path, INI_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &ini_path).stdout(is_empty()).stderr(is_empty());
+    read(&ini_path).assert(is_match(INI_CONTENT_ENCRYPTED_PATTERN).unwrap());
+    yage!("decrypt", "-K", &key_path, "-i", &ini_path).stdout(is_empty()).stderr(is_empty());
+    assert_eq!(read(&ini_path), INI_CONTENT);
+}
+
+#[test]
+fn ini_env_flatten() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let ini_path = tmp.child("app.ini");
+    write(&ini_path, INI_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &ini_path);
+    yage!("env", "-K", &key_path, "--flatten", &ini_path, "env")
+        .stdout(contains("NAME=app\n").and(contains("DATABASE_HOST=localhost\n")))
+        .stderr(is_empty());
+}
+
+#[test]
+fn dotenv_high_entropy_comment() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let env_path = tmp.child("app.env");
+    write(
+        &env_path,
+        "# database\nDB_HOST=localhost # this comment contains a secret key A1B2C3D4E5F6G7H8I9J0K1L2M3N4O5P6\n",
+    );
+    yage!("encrypt", "-R", &pub_path, &env_path)
+        .stderr(contains("app.env:2:19: high-entropy token detected"));
+    yage_cmd!("encrypt", "-R", &pub_path, "--on-comment-secret", "fail", &env_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("high-entropy token detected in a comment"));
+    yage_cmd!("encrypt", "-R", &pub_path, "--on-comment-secret", "encrypt", &env_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("the comments of the dotenv files can't be encrypted"));
+    let ini_path = tmp.child("app.ini");
+    write(
+        &ini_path,
+        "; this comment contains a secret key A1B2C3D4E5F6G7H8I9J0K1L2M3N4O5P6\nname = app\n",
+    );
+    yage_cmd!("check", "--on-comment-secret", "fail", &ini_path)
+        .assert()
+        .failure()
+        .stderr(contains("app.ini:1:1: high-entropy token detected"));
+}
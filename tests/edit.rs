@@ -2,6 +2,7 @@ mod common;
 
 use assert_fs::prelude::*;
 use common::*;
+use predicates::prelude::*;
 use predicates::str::{contains, is_empty};
 use pretty_assertions::{assert_eq, assert_ne};
 use yage::{EncryptionStatus, check_encrypted};
@@ -30,6 +31,35 @@ fn edit_emits_warning_on_high_entropy_comment() {
         .stderr(contains("high-entropy token detected"));
 }
 
+#[cfg(not(windows))]
+#[test]
+fn edit_decrypts_encrypted_comments() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT);
+    yage!("encrypt", "-R", &pub_path, "--on-comment-secret", "encrypt", "-i", &yaml_path);
+    let encrypted_comment =
+        read(&yaml_path).lines().find(|l| l.starts_with("# yage[")).unwrap().to_owned();
+    // the editor keeps a copy of the buffer
+    let buffer_path = tmp.child("buffer.yaml");
+    let editor = format!("bash -c 'cp $0 {}; echo hop: hop >> $0'", buffer_path.to_string_lossy());
+    yage!("edit", "-K", &key_path, "-e", editor.as_str(), &yaml_path);
+    assert!(read(&buffer_path).starts_with(YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT));
+    // the unchanged comment keeps its encrypted value
+    assert!(read(&yaml_path).contains(&encrypted_comment));
+    yage!("decrypt", "-K", &key_path, &yaml_path)
+        .stdout(format!("{YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT}hop: hop\n"));
+
+    // the comment is encrypted to the new recipients
+    let (other_key_path, other_pub_path) = create_key(&tmp);
+    yage!("edit", "-K", &key_path, "-e", "true", "-R", &other_pub_path, &yaml_path);
+    assert!(!read(&yaml_path).contains(&encrypted_comment));
+    assert!(!read(&yaml_path).contains("A1B2C3D4"));
+    yage!("decrypt", "-K", &other_key_path, &yaml_path)
+        .stdout(format!("{YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT}hop: hop\n"));
+}
+
 #[cfg(not(windows))]
 #[test]
 fn edit_key_file_from_args() {
@@ -104,3 +134,307 @@ fn edit_empty() {
         .stdout(is_empty())
         .stderr(contains("error: the following required arguments were not provided"));
 }
+
+#[cfg(not(windows))]
+#[test]
+fn edit_merges_concurrent_changes() {
+    let (_tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    let before_edit_data = read(&encrypted_path);
+    // the editor adds a value, while another value is added to the original file
+    let editor = format!(
+        "bash -c 'echo hop: hop >> $0 && echo other: value >> {}'",
+        encrypted_path.to_string_lossy()
+    );
+    yage_cmd!("edit", "-K", &key_path, "-e", editor.as_str(), &encrypted_path)
+        .assert()
+        .success()
+        .stdout(is_empty())
+        .stderr(contains("file modified during the edit").and(contains("changes merged")));
+    let after_edit_data = read(&encrypted_path);
+    assert!(after_edit_data.starts_with(&before_edit_data));
+    assert!(after_edit_data.contains("other: value\n"));
+    let decrypted = yage!("decrypt", "-K", &key_path, &encrypted_path).get_output().stdout.clone();
+    let decrypted = String::from_utf8(decrypted).unwrap();
+    assert!(decrypted.contains("hop: hop\n"));
+    assert!(decrypted.contains("other: value\n"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_aborts_on_conflicting_changes() {
+    let (_tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    // the editor and another process change the same value
+    let editor = format!(
+        "bash -c 'sed -i s/^foo:.*/foo:\\ edited/ $0 && sed -i s/^foo:.*/foo:\\ other/ {}'",
+        encrypted_path.to_string_lossy()
+    );
+    let output = yage_cmd!("edit", "-K", &key_path, "-e", editor.as_str(), &encrypted_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("was modified during the edit"))
+        .get_output()
+        .clone();
+    assert!(read(&encrypted_path).starts_with("foo: other\n"));
+    // the edited file is kept
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let edited = stderr.rsplit(" kept in \"").next().unwrap().trim_end().trim_end_matches('"');
+    let edited = std::path::PathBuf::from(edited);
+    assert!(read(&edited).starts_with("foo: edited\n"));
+    std::fs::remove_dir_all(edited.parent().unwrap()).unwrap();
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_merges_disjoint_sequence_changes() {
+    let (tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    // the editor modifies the first item of the array, while its last item is modified in the
+    // original file
+    let script_path = tmp.child("editor.sh");
+    write(
+        &script_path,
+        &format!(
+            "sed -i 's/^- 1$/- 10/' \"$1\"\nsed -i '/^- yage/{{n;n;s/.*/- 30/}}' {:?}\n",
+            encrypted_path
+        ),
+    );
+    let editor = format!("bash {}", script_path.to_string_lossy());
+    yage_cmd!("edit", "-K", &key_path, "-e", editor.as_str(), &encrypted_path)
+        .assert()
+        .success()
+        .stdout(is_empty())
+        .stderr(contains("file modified during the edit").and(contains("changes merged")));
+    let decrypted = yage!("decrypt", "-K", &key_path, &encrypted_path).get_output().stdout.clone();
+    let decrypted = String::from_utf8(decrypted).unwrap();
+    assert!(decrypted.contains("array:\n- 10\n- 2\n- 30\n"), "{decrypted}");
+}
+
+// editor script that saves an invalid YAML file the first time, and fixes it the second time
+#[cfg(not(windows))]
+const INVALID_EDITOR_SCRIPT: &str = r#"
+if [ -e "$1.invalid" ]; then
+  grep -q '^# yage error: ' "$1" || exit 1
+  sed -i '/^invalid/d' "$1"
+  echo 'hop: hop' >> "$1"
+else
+  touch "$1.invalid"
+  echo 'invalid: [yaml' >> "$1"
+fi
+"#;
+
+#[cfg(not(windows))]
+#[test]
+fn edit_reopens_editor_on_invalid_yaml() {
+    let (tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    let script_path = tmp.child("editor.sh");
+    write(&script_path, INVALID_EDITOR_SCRIPT);
+    let editor = format!("bash {}", script_path.to_string_lossy());
+    let before_edit_data = read(&encrypted_path);
+    yage_cmd!("edit", "-K", &key_path, "-e", editor.as_str(), &encrypted_path)
+        .write_stdin("\n")
+        .assert()
+        .success()
+        .stdout(is_empty())
+        .stderr(contains("Parse error at line").and(contains("Re-open the editor? [Y/n]")));
+    let after_edit_data = read(&encrypted_path);
+    assert!(after_edit_data.starts_with(&before_edit_data));
+    assert!(after_edit_data.contains("\nhop: yage["));
+    assert!(!after_edit_data.contains("invalid"));
+    assert!(!after_edit_data.contains("yage error"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_aborts_on_invalid_yaml() {
+    let (tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    let script_path = tmp.child("editor.sh");
+    write(&script_path, INVALID_EDITOR_SCRIPT);
+    let editor = format!("bash {}", script_path.to_string_lossy());
+    let before_edit_data = read(&encrypted_path);
+    // an unrecognized answer is asked again
+    let output = yage_cmd!("edit", "-K", &key_path, "-e", editor.as_str(), &encrypted_path)
+        .write_stdin("yse\nn\n")
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("Parse error at line").and(contains("edit aborted")))
+        .stderr(contains("Please answer yes or no."))
+        .get_output()
+        .clone();
+    assert_eq!(read(&encrypted_path), before_edit_data);
+    // the edited file is kept
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let edited = stderr.rsplit(" kept in \"").next().unwrap().trim_end().trim_end_matches('"');
+    let edited = std::path::PathBuf::from(edited);
+    assert!(read(&edited).contains("invalid: [yaml"));
+    std::fs::remove_dir_all(edited.parent().unwrap()).unwrap();
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_unchanged() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT_WITH_COMMENTS);
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    let before_edit_data = read(&yaml_path);
+    yage!("edit", "-K", &key_path, "-e", "true", &yaml_path).stdout(is_empty()).stderr(is_empty());
+    assert_eq!(read(&yaml_path), before_edit_data);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_preserves_comment_changes() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT_WITH_COMMENTS);
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    let before_edit_data = read(&yaml_path);
+    let script_path = tmp.child("editor.sh");
+    write(
+        &script_path,
+        r#"
+sed -i \
+  -e 's/^# Top-level comment.*/# Edited top-level comment/' \
+  -e 's/# nested comment inside titi/# edited nested comment/' \
+  -e 's/^  - 3/  # new comment before item 3\n  - 3/' \
+  -e 's/^# final comment/\n# edited final comment/' \
+  "$1"
+"#,
+    );
+    let editor = format!("bash {}", script_path.to_string_lossy());
+    yage!("edit", "-K", &key_path, "-e", editor.as_str(), &yaml_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    let after_edit_data = read(&yaml_path);
+    // the values are not re-encrypted
+    let values = |s: &str| s.lines().filter(|l| l.contains("yage[")).map(String::from).collect();
+    let before_values: Vec<String> = values(&before_edit_data);
+    let after_values: Vec<String> = values(&after_edit_data);
+    assert_eq!(after_values, before_values);
+    let expected = YAML_CONTENT_WITH_COMMENTS
+        .replace("# Top-level comment describing the config", "# Edited top-level comment")
+        .replace("# nested comment inside titi", "# edited nested comment")
+        .replace("  - 3\n", "  # new comment before item 3\n  - 3\n")
+        .replace("# final comment", "\n# edited final comment");
+    yage!("decrypt", "-K", &key_path, &yaml_path).stdout(expected).stderr(is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_keeps_shifted_sequence_values() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(
+        &yaml_path,
+        "list:\n- a\n- b\n- c\nusers:\n- name: alice\n  password: secret1\n- name: bob\n  password: secret2\n",
+    );
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    let before_edit_data = read(&yaml_path);
+    let script_path = tmp.child("editor.sh");
+    write(
+        &script_path,
+        r#"
+sed -i \
+  -e 's/^- a$/- new\n- a/' \
+  -e '/^- b$/d' \
+  -e 's/^- name: alice$/- name: carol\n  password: secret3\n- name: alice/' \
+  -e 's/^- name: bob$/- name: robert/' \
+  "$1"
+"#,
+    );
+    let editor = format!("bash {}", script_path.to_string_lossy());
+    yage!("edit", "-K", &key_path, "-e", editor.as_str(), &yaml_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    let after_edit_data = read(&yaml_path);
+    let values = |s: &str| s.lines().filter(|l| l.contains("yage[")).map(String::from).collect();
+    let before_values: Vec<String> = values(&before_edit_data);
+    let after_values: Vec<String> = values(&after_edit_data);
+    // a, c, alice, secret1 and secret2 are unchanged, bob is removed
+    for i in [0, 2, 3, 4, 6] {
+        assert!(after_values.contains(&before_values[i]), "{} was re-encrypted", before_values[i]);
+    }
+    assert!(!after_values.contains(&before_values[5]));
+    assert_eq!(after_values.len(), 9);
+    yage!("decrypt", "-K", &key_path, &yaml_path)
+        .stdout(
+            "list:\n- new\n- a\n- c\nusers:\n- name: carol\n  password: secret3\n- name: alice\n  password: secret1\n- name: robert\n  password: secret2\n",
+        )
+        .stderr(is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_overwrites_decrypted_file() {
+    let (tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    let edit_dir = tmp.child("edit");
+    edit_dir.create_dir_all().unwrap();
+    let leak_path = tmp.child("leak.yaml");
+    let script_path = tmp.child("editor.sh");
+    // keep a hard link to the decrypted file, to check its content after the edit
+    write(&script_path, &format!(r#"ln "$1" {:?} && echo "hop: hop" >> "$1""#, leak_path.path()));
+    let editor = format!("bash {}", script_path.to_string_lossy());
+    yage!("edit", "-K", &key_path, "-e", editor.as_str(), "--tmpdir", &edit_dir, &encrypted_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    let leaked = std::fs::read(&leak_path).unwrap();
+    assert!(!leaked.is_empty());
+    assert!(leaked.iter().all(|&b| b == 0));
+    assert_eq!(std::fs::read_dir(&edit_dir).unwrap().count(), 0);
+    yage!("decrypt", "-K", &key_path, &encrypted_path)
+        .stdout(contains("hop: hop"))
+        .stderr(is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_creates_new_file() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("new.yaml");
+    yage!("edit", "-e", EDITOR, "-R", &pub_path, &yaml_path).stdout(is_empty()).stderr(is_empty());
+    yage!("recipients", "-r", &yaml_path).stdout(read(&pub_path)).stderr(is_empty());
+    yage!("decrypt", "-K", &key_path, &yaml_path).stdout("hop: hop\n").stderr(is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_does_not_create_empty_file() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("new.yaml");
+    yage!("edit", "-e", "true", "-R", &pub_path, &yaml_path)
+        .stdout(is_empty())
+        .stderr(contains("empty file not created"));
+    yaml_path.assert(predicate::path::missing());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn edit_changes_recipients() {
+    let (tmp, key_path, pub_path, _, encrypted_path) = generate_encrypted_file();
+    let (other_key_path, other_pub_path) = create_key(&tmp);
+    let before_edit_data = read(&encrypted_path);
+    yage!("edit", "-K", &key_path, "-e", "true", "-R", &other_pub_path, &encrypted_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    // all the values are encrypted again for the new recipient
+    let after_edit_data = read(&encrypted_path);
+    assert_ne!(after_edit_data, before_edit_data);
+    yage!("decrypt", "-K", &other_key_path, &encrypted_path)
+        .stdout(YAML_CONTENT)
+        .stderr(is_empty());
+
+    yage!("edit", "-K", &other_key_path, "-e", "true", "-D", &pub_path, &encrypted_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    yage!("recipients", "-r", &encrypted_path).stdout(read(&other_pub_path)).stderr(is_empty());
+    yage_cmd!("decrypt", "-K", &key_path, &encrypted_path)
+        .assert()
+        .failure()
+        .stderr(contains("No matching keys found"));
+}
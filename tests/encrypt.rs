@@ -2,9 +2,10 @@ mod common;
 
 use assert_fs::prelude::*;
 use predicates::prelude::predicate::str::*;
+use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use std::{fs::OpenOptions, io::Write};
-use yage::EncryptionStatus;
+use yage::{EncryptionStatus, check_encrypted};
 
 use crate::common::*;
 
@@ -181,6 +182,96 @@ fn encrypt_in_place() {
     }
 }
 
+#[test]
+fn encrypt_in_place_backup() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", "--backup", &yaml_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    assert_eq!(read(&tmp.child("file.yaml.bak")), YAML_CONTENT);
+    assert_eq!(check_encrypted(&parse_yaml(&read(&yaml_path))), EncryptionStatus::Encrypted);
+    assert_no_temp_files(&tmp);
+}
+
+#[test]
+fn encrypt_output_new_file() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &encrypted_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    assert_eq!(check_encrypted(&parse_yaml(&read(&encrypted_path))), EncryptionStatus::Encrypted);
+    // the new files are created with the default permissions
+    let reference_path = tmp.child("reference.yaml");
+    write(&reference_path, "");
+    assert_eq!(
+        std::fs::metadata(&encrypted_path).unwrap().permissions(),
+        std::fs::metadata(&reference_path).unwrap().permissions()
+    );
+    assert_no_temp_files(&tmp);
+}
+
+#[cfg(unix)]
+#[test]
+fn encrypt_output_special_files() {
+    use std::os::unix::fs::FileTypeExt;
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", "/dev/null")
+        .stdout(is_empty())
+        .stderr(is_empty());
+    assert!(std::fs::metadata("/dev/null").unwrap().file_type().is_char_device());
+    // the output may be a named pipe
+    let fifo_path = tmp.child("fifo");
+    let status = std::process::Command::new("mkfifo").arg(fifo_path.path()).status().unwrap();
+    assert!(status.success());
+    let reader = {
+        let fifo_path = fifo_path.path().to_owned();
+        std::thread::spawn(move || std::fs::read_to_string(fifo_path).unwrap())
+    };
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &fifo_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    assert_eq!(check_encrypted(&parse_yaml(&reader.join().unwrap())), EncryptionStatus::Encrypted);
+    assert!(std::fs::metadata(&fifo_path).unwrap().file_type().is_fifo());
+}
+
+#[cfg(unix)]
+#[test]
+fn encrypt_in_place_preserves_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    std::fs::set_permissions(&yaml_path, std::fs::Permissions::from_mode(0o640)).unwrap();
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path).stdout(is_empty()).stderr(is_empty());
+    let mode = std::fs::metadata(&yaml_path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+}
+
+#[cfg(unix)]
+#[test]
+fn encrypt_in_place_follows_symlinks() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    let link_path = tmp.child("link.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    std::os::unix::fs::symlink(yaml_path.path(), link_path.path()).unwrap();
+    yage!("encrypt", "-R", &pub_path, "-i", &link_path).stdout(is_empty()).stderr(is_empty());
+    assert!(std::fs::symlink_metadata(&link_path).unwrap().file_type().is_symlink());
+    assert_eq!(check_encrypted(&parse_yaml(&read(&yaml_path))), EncryptionStatus::Encrypted);
+}
+
 #[test]
 fn encrypt_stdin_in_place() {
     yage_cmd!("encrypt", "--in-place", "-")
@@ -423,6 +514,64 @@ fn encrypt_suppresses_comment_warning_with_quiet() {
         .stderr(is_empty());
 }
 
+#[test]
+fn encrypt_fails_on_high_entropy_comment() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT);
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage_cmd!(
+        "encrypt",
+        "-R",
+        &pub_path,
+        &yaml_path,
+        "-o",
+        &encrypted_path,
+        "--on-comment-secret",
+        "fail"
+    )
+    .assert()
+    .failure()
+    .stdout(is_empty())
+    .stderr(contains("high-entropy token detected in a comment"));
+    encrypted_path.assert(predicates::path::missing());
+}
+
+#[test]
+fn encrypt_high_entropy_comment() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT);
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!(
+        "encrypt",
+        "-R",
+        &pub_path,
+        &yaml_path,
+        "-o",
+        &encrypted_path,
+        "--on-comment-secret",
+        "encrypt"
+    )
+    .stdout(is_empty())
+    .stderr(is_empty());
+    let encrypted_data = read(&encrypted_path);
+    encrypted_data.assert(
+        starts_with("# Top-level comment describing the config\n# yage[")
+            .and(contains("A1B2C3D4E5F6G7H8I9J0K1L2M3N4O5P6").not()),
+    );
+    // the encrypted comment is not detected as a secret anymore
+    yage!("check", &encrypted_path).stdout(is_empty()).stderr(is_empty());
+    // and is restored by decrypt
+    yage!("decrypt", "-K", &key_path, &encrypted_path)
+        .stdout(starts_with(
+            YAML_CONTENT_WITH_HIGH_ENTROPY_COMMENT.lines().take(2).collect::<Vec<_>>().join("\n"),
+        ))
+        .stderr(is_empty());
+}
+
 #[test]
 fn encrypt_decrypt_scalar_string_roundtrip() {
     let tmp = temp_dir();
@@ -24,6 +24,32 @@ fn env_key_file_from_args() {
         .stderr(is_empty());
 }
 
+#[test]
+fn env_command_separator() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &encrypted_path);
+    yage!("env", "-K", &key_path, &encrypted_path, "--", "sh", "-c", "echo $foo")
+        .stdout("bar\n")
+        .stderr(is_empty());
+    // the options may follow the file
+    yage!("env", &encrypted_path, "-K", &key_path, "env")
+        .stdout(contains("foo=bar"))
+        .stderr(is_empty());
+    yage!("env", &encrypted_path, "-K", &key_path, "sh", "-c", "echo $foo")
+        .stdout("bar\n")
+        .stderr(is_empty());
+    yage_cmd!("env", "-K", &key_path, &encrypted_path, "--")
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(is_empty())
+        .stderr(contains("error: no command to run was given"));
+}
+
 #[test]
 fn env_key_from_args() {
     let tmp = temp_dir();
@@ -103,6 +129,306 @@ fn env_no_command() {
     yage_cmd!("env", "foo.yaml")
         .assert()
         .failure()
+        .code(2)
         .stdout(is_empty())
-        .stderr(contains("error: the following required arguments were not provided"));
+        .stderr(contains("error: no command to run was given"))
+        .stderr(contains("Usage: yage env"));
+}
+
+const NESTED_YAML_CONTENT: &str = "\
+database:
+  host: localhost
+  password: secret
+  replica-hosts:
+    - db1
+    - db2
+services:
+  api:
+    port: 8080
+";
+
+#[test]
+fn env_flatten() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, NESTED_YAML_CONTENT);
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &encrypted_path);
+    yage!("env", "-K", &key_path, "--flatten", &encrypted_path, "env")
+        .stdout(contains("DATABASE_HOST=localhost\n"))
+        .stdout(contains("DATABASE_PASSWORD=secret\n"))
+        .stdout(contains("DATABASE_REPLICA_HOSTS=[\"db1\",\"db2\"]\n"))
+        .stdout(contains("SERVICES_API_PORT=8080\n"))
+        .stderr(is_empty());
+    yage!(
+        "env",
+        "-K",
+        &key_path,
+        "--flatten",
+        "--separator",
+        "__",
+        "--case",
+        "preserve",
+        "--prefix",
+        "APP_",
+        &encrypted_path,
+        "env"
+    )
+    .stdout(contains("APP_database__host=localhost\n"))
+    .stdout(contains("APP_services__api__port=8080\n"))
+    .stderr(is_empty());
+    for separator in ["", "="] {
+        yage_cmd!(
+            "env",
+            "-K",
+            &key_path,
+            "--flatten",
+            "--separator",
+            separator,
+            &encrypted_path,
+            "env"
+        )
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(is_empty())
+        .stderr(contains("invalid separator"));
+    }
+}
+
+#[test]
+fn env_path() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, NESTED_YAML_CONTENT);
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &encrypted_path);
+    yage!("env", "-K", &key_path, "--path", "services.api", &encrypted_path, "env")
+        .stdout(contains("port=8080\n"))
+        .stderr(is_empty());
+    yage!("env", "-K", &key_path, "--flatten", "--path", "database", &encrypted_path, "env")
+        .stdout(contains("HOST=localhost\n"))
+        .stdout(contains("REPLICA_HOSTS=[\"db1\",\"db2\"]\n"))
+        .stderr(is_empty());
+    yage_cmd!("env", "-K", &key_path, "--path", "database.port", &encrypted_path, "env")
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("path not found in the YAML file: database.port"));
+}
+
+#[test]
+fn env_multiple_files() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    let common_path = tmp.child("common.enc.yaml");
+    let prod_path = tmp.child("prod.enc.yaml");
+    write(&yaml_path, "foo: bar\nbaz: common\n");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &common_path);
+    write(&yaml_path, "baz: prod\nqux: quux\n");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &prod_path);
+    yage!("env", "-K", &key_path, "-f", &common_path, "--file", &prod_path, "--", "env")
+        .stdout(contains("foo=bar\n"))
+        .stdout(contains("baz=prod\n"))
+        .stdout(contains("qux=quux\n"))
+        .stderr(is_empty());
+    yage_cmd!("env", "-K", &key_path, "--strict", "-f", &common_path, "-f", &prod_path, "env")
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("prod.enc.yaml\": variable baz already defined in a previous file"));
+    yage!("env", "-K", &key_path, "-f", &common_path, "sh", "-c", "echo $foo")
+        .stdout("bar\n")
+        .stderr(is_empty());
+    yage_cmd!("env", "-K", &key_path, "-f", &common_path)
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(is_empty())
+        .stderr(contains("error: no command to run was given"));
+}
+
+#[cfg(unix)]
+#[test]
+fn env_exit_status() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    yage_cmd!("env", "-K", &key_path, &yaml_path, "sh", "-c", "exit 3").assert().code(3);
+    // yage is replaced by the command, so it is killed by the signal
+    yage_cmd!("env", "-K", &key_path, &yaml_path, "sh", "-c", "kill -TERM $$")
+        .assert()
+        .interrupted();
+    // the signal number is in the exit code of the supervising process
+    yage_cmd!("env", "-K", &key_path, "--supervise", &yaml_path, "sh", "-c", "kill -TERM $$")
+        .assert()
+        .code(128 + 15);
+}
+
+#[cfg(unix)]
+#[test]
+fn env_supervise_forwards_signals() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin("yage"))
+        .args(["env", "--supervise", "-K"])
+        .arg(&key_path)
+        .arg(yaml_path.path())
+        .args([
+            "sh",
+            "-c",
+            "trap 'echo \"got TERM $foo\"; exit 7' TERM; echo ready; while true; do sleep 0.1; done",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "ready\n");
+    let status = Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+    assert!(status.success());
+    line.clear();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "got TERM bar\n");
+    assert_eq!(child.wait().unwrap().code(), Some(7));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn env_supervise_doesnt_forward_signals_back() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    // the signal sent by the command to yage is not sent back to the command
+    yage!(
+        "env",
+        "-K",
+        &key_path,
+        "--supervise",
+        &yaml_path,
+        "sh",
+        "-c",
+        "trap 'echo got USR1' USR1; kill -USR1 $PPID; sleep 0.5; echo done"
+    )
+    .stdout("done\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn env_watch() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    let encrypted_path = tmp.child("file.enc.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &encrypted_path);
+    // like in the README
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin("yage"))
+        .args(["env", "-K"])
+        .arg(&key_path)
+        .args(["--watch", "--watch-interval", "0.1"])
+        .arg(encrypted_path.path())
+        .args([
+            "--",
+            "sh",
+            "-c",
+            "trap 'echo stopped; exit 0' TERM; echo \"foo=$foo\"; while true; do sleep 0.1; done",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut read_line = || {
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        line
+    };
+    assert_eq!(read_line(), "foo=bar\n");
+    // the file is replaced atomically, like in a mounted ConfigMap
+    write(&yaml_path, "foo: baz");
+    let new_path = tmp.child("new.enc.yaml");
+    yage!("encrypt", "-R", &pub_path, &yaml_path, "-o", &new_path);
+    std::fs::rename(new_path.path(), encrypted_path.path()).unwrap();
+    assert_eq!(read_line(), "stopped\n");
+    assert_eq!(read_line(), "foo=baz\n");
+    // a file that can't be decrypted doesn't stop the command
+    write(&encrypted_path, "foo: yage[invalid|r:invalid]");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let status = Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+    assert!(status.success());
+    assert_eq!(read_line(), "stopped\n");
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+}
+
+#[cfg(unix)]
+#[test]
+fn env_watch_terminated_during_restart() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::{Command, Stdio};
+
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin("yage"))
+        .args(["env", "--watch", "--watch-interval", "0.1", "-K"])
+        .arg(&key_path)
+        .arg(yaml_path.path())
+        .args([
+            "sh",
+            "-c",
+            "trap 'echo stopping; sleep 1; exit 0' TERM; echo \"foo=$foo\"; while true; do sleep 0.1; done",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "foo=bar\n");
+    write(&yaml_path, "foo: baz\n");
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    line.clear();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "stopping\n");
+    // yage is terminated while the command is stopping, so it is not started again
+    let status = Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+    assert!(status.success());
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert!(!rest.contains("foo=baz"), "{rest}");
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+}
+
+#[cfg(unix)]
+#[test]
+fn env_watch_exit_code() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    yage_cmd!("env", "--watch", "-K", &key_path, &yaml_path, "sh", "-c", "exit 5").assert().code(5);
+    yage_cmd!("env", "--watch-interval", "0", "-K", &key_path, &yaml_path, "true")
+        .assert()
+        .failure()
+        .stderr(contains("invalid interval: 0"));
 }
@@ -0,0 +1,121 @@
+#![cfg(not(windows))]
+
+mod common;
+
+use assert_fs::fixture::PathChild;
+use common::*;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::path::Path;
+
+#[test]
+fn exec_values_in_files() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, "foo: bar\npassword: s3cr3t\n");
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    let output = yage!(
+        "exec",
+        "-K",
+        &key_path,
+        &yaml_path,
+        "sh",
+        "-c",
+        "echo $YAGE_SECRETS_DIR; stat -c %a $YAGE_SECRETS_DIR $YAGE_SECRETS_DIR/password; \
+         cat $YAGE_SECRETS_DIR/foo $YAGE_SECRETS_DIR/password; echo; env | grep -c s3cr3t || true"
+    )
+    .stdout(contains("\n700\n600\nbars3cr3t\n0\n"))
+    .get_output()
+    .stdout
+    .clone();
+    // the directory has been removed
+    let dir = String::from_utf8(output).unwrap().lines().next().unwrap().to_owned();
+    assert!(!Path::new(&dir).exists());
+}
+
+#[test]
+fn exec_documented_example() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("secrets.yaml");
+    write(&yaml_path, "DB_PASSWORD: s3cr3t\n");
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    yage!(
+        "exec",
+        "-K",
+        &key_path,
+        &yaml_path,
+        "--",
+        "sh",
+        "-c",
+        "echo --db-password-file \"$YAGE_SECRETS_DIR/DB_PASSWORD\"; cat $YAGE_SECRETS_DIR/DB_PASSWORD"
+    )
+    .stdout(contains("/DB_PASSWORD\ns3cr3t").and(predicate::str::starts_with("--db-password-file ")));
+}
+
+#[test]
+fn exec_options() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, "db:\n  host: localhost\n  password: s3cr3t\n");
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    yage!(
+        "exec",
+        "-K",
+        &key_path,
+        "--flatten",
+        "--case",
+        "lower",
+        "--dir-var",
+        "SECRETS",
+        "--files",
+        &yaml_path,
+        "--",
+        "sh",
+        "-c",
+        "ls $SECRETS; cat $SECRETS/db_password"
+    )
+    .stdout("db_host\ndb_password\ns3cr3t");
+    // the exit code of the command
+    yage_cmd!("exec", "-K", &key_path, "--path", "db", &yaml_path, "sh", "-c", "exit 3")
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn exec_fifo() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, "password: s3cr3t\n");
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    yage!(
+        "exec",
+        "-K",
+        &key_path,
+        "--fifo",
+        &yaml_path,
+        "sh",
+        "-c",
+        "test -p $YAGE_SECRETS_DIR/password && cat $YAGE_SECRETS_DIR/password; echo; \
+         for i in 1 2 3 4 5 6 7 8 9 10; do test -e $YAGE_SECRETS_DIR/password || break; sleep 0.1; done; \
+         test -e $YAGE_SECRETS_DIR/password || echo deleted"
+    )
+    .stdout("s3cr3t\ndeleted\n");
+}
+
+#[test]
+fn exec_invalid_file_name() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, "a/b: s3cr3t\n");
+    yage!("encrypt", "-i", "-R", &pub_path, &yaml_path);
+    yage_cmd!("exec", "-K", &key_path, &yaml_path, "true")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(contains("invalid file name").and(contains("s3cr3t").not()));
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use assert_fs::fixture::PathChild;
+use common::*;
+use predicates::str::{contains, is_empty};
+
+const EXPORT_YAML_CONTENT: &str = r#"foo: bar
+quote: 'it''s "quoted"'
+multiline: |
+  line 1
+  line 2
+"#;
+
+#[test]
+fn export_sh() {
+    let (_tmp, key_path, _, _, encrypted_path) = generate_encrypted_file_with(EXPORT_YAML_CONTENT);
+    yage!("export", "-K", &key_path, &encrypted_path)
+        .stdout(
+            "export foo=bar\nexport multiline='line 1\nline 2\n'\nexport quote=\"it's \\\"quoted\\\"\"\n",
+        )
+        .stderr(is_empty());
+}
+
+#[test]
+fn export_dotenv() {
+    let (_tmp, key_path, _, _, encrypted_path) = generate_encrypted_file_with(EXPORT_YAML_CONTENT);
+    yage!("export", "-K", &key_path, "--format", "dotenv", &encrypted_path)
+        .stdout("foo='bar'\nmultiline=\"line 1\\nline 2\\n\"\nquote=\"it's \\\"quoted\\\"\"\n")
+        .stderr(is_empty());
+}
+
+#[test]
+fn export_dotenv_no_expansion() {
+    let (_tmp, key_path, _, _, encrypted_path) =
+        generate_encrypted_file_with("password: \"a'$b\\\\c\"\nplain: $HOME\n");
+    yage!("export", "-K", &key_path, "--format", "dotenv", &encrypted_path)
+        .stdout("password=\"a'\\$b\\\\c\"\nplain='$HOME'\n")
+        .stderr(is_empty());
+}
+
+#[test]
+fn export_fish_and_powershell() {
+    let (_tmp, key_path, _, _, encrypted_path) = generate_encrypted_file_with(EXPORT_YAML_CONTENT);
+    yage!("export", "-K", &key_path, "-F", "fish", &encrypted_path)
+        .stdout(contains("set -gx quote 'it\\'s \"quoted\"'\n"))
+        .stderr(is_empty());
+    yage!("export", "-K", &key_path, "-F", "powershell", &encrypted_path)
+        .stdout(contains("$Env:quote = 'it''s \"quoted\"'\n"))
+        .stderr(is_empty());
+}
+
+#[test]
+fn export_docker() {
+    let (tmp, key_path, _, _, encrypted_path) = generate_encrypted_file_with(EXPORT_YAML_CONTENT);
+    let yaml_path = tmp.child("simple.yaml");
+    write(&yaml_path, "foo: bar baz\n");
+    yage!("export", "-K", &key_path, "-F", "docker", &yaml_path)
+        .stdout("foo=bar baz\n")
+        .stderr(is_empty());
+    yage_cmd!("export", "-K", &key_path, "-F", "docker", &encrypted_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("multiline: variable can't be exported in the docker format"));
+}
+
+#[test]
+fn export_json() {
+    let (_tmp, key_path, _, _, encrypted_path) = generate_encrypted_file_with(EXPORT_YAML_CONTENT);
+    yage!("export", "-K", &key_path, "-F", "json", &encrypted_path)
+        .stdout(
+            "{\n  \"foo\": \"bar\",\n  \"multiline\": \"line 1\\nline 2\\n\",\n  \"quote\": \"it's \\\"quoted\\\"\"\n}\n",
+        )
+        .stderr(is_empty());
+}
+
+#[test]
+fn export_empty() {
+    yage_cmd!("export")
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("error: the following required arguments were not provided"));
+}
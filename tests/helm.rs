@@ -0,0 +1,121 @@
+#![cfg(not(windows))]
+
+mod common;
+
+use assert_fs::TempDir;
+use assert_fs::fixture::{ChildPath, PathChild};
+use common::*;
+use predicates::prelude::*;
+use predicates::str::{contains, is_empty};
+use std::path::Path;
+
+/// A fake helm command that prints its arguments, and the content of the files passed as arguments.
+fn fake_helm(tmp: &TempDir) -> String {
+    let script = tmp.child("helm.sh");
+    write(
+        &script,
+        r#"for arg in "$@"; do
+    echo "arg: $arg"
+    files="${arg#--values=}"
+    for file in $(echo "${files#-f}" | tr , ' '); do
+        if [ -f "$file" ]; then
+            echo "$file" >> "$(dirname "$0")/files"
+            cat "$file"
+        fi
+    done
+done
+"#,
+    );
+    format!("sh {}", shlex::try_quote(script.path().to_str().unwrap()).unwrap())
+}
+
+fn encrypted_values(tmp: &TempDir, pub_path: &Path) -> ChildPath {
+    let values = tmp.child("secrets.yaml");
+    write(&values, "password: s3cr3t\n");
+    yage!("encrypt", "-i", "-R", pub_path, &values);
+    values
+}
+
+#[test]
+fn helm_decrypts_values_files() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let values = encrypted_values(&tmp, &pub_path);
+    let plain = tmp.child("plain.yaml");
+    write(&plain, "replicas: 2\n");
+    yage!(
+        "helm",
+        "--helm",
+        fake_helm(&tmp),
+        "-K",
+        &key_path,
+        "upgrade",
+        "api",
+        "./chart",
+        "-f",
+        &values,
+        "--values",
+        &plain
+    )
+    .stdout(
+        contains("arg: upgrade\narg: api\narg: ./chart\narg: -f\n")
+            .and(contains("password: s3cr3t\n"))
+            .and(contains(format!("arg: {}\nreplicas: 2\n", plain.path().display())))
+            .and(contains("yage[").not()),
+    );
+    // the decrypted file has been removed
+    let files = read(&tmp.child("files"));
+    let decrypted = files.lines().next().unwrap();
+    assert!(decrypted.ends_with("0-values.yaml"));
+    assert!(!Path::new(decrypted).exists());
+}
+
+#[test]
+fn helm_values_option_forms() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let values = encrypted_values(&tmp, &pub_path);
+    let plain = tmp.child("plain.yaml");
+    write(&plain, "replicas: 2\n");
+    let values = values.path().to_str().unwrap();
+    let plain = plain.path().to_str().unwrap();
+    yage!(
+        "helm",
+        "--helm",
+        fake_helm(&tmp),
+        "-K",
+        &key_path,
+        "template",
+        format!("--values={plain},{values}"),
+        format!("-f{values}")
+    )
+    .stdout(
+        contains(format!("arg: --values={plain},"))
+            .and(contains("password: s3cr3t\n").count(2))
+            .and(contains("yage[").not()),
+    );
+}
+
+#[test]
+fn helm_exit_code() {
+    yage_cmd!("helm", "--helm", "sh -c 'exit 3'", "version").assert().code(3);
+    yage_cmd!("helm", "--helm", "", "version")
+        .assert()
+        .code(2)
+        .stdout(is_empty())
+        .stderr(contains("error: invalid helm command: \"\""));
+}
+
+#[test]
+fn helm_no_keys() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let values = encrypted_values(&tmp, &pub_path);
+    yage_cmd!("helm", "--helm", fake_helm(&tmp), "install", "api", "-f", &values)
+        .env_remove("YAGE_KEY")
+        .env_remove("YAGE_KEY_FILE")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(contains("no keys"));
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use assert_fs::fixture::PathChild;
+use common::*;
+use predicates::str::{contains, is_empty};
+use yage::{EncryptionStatus, check_encrypted};
+
+#[test]
+fn import_dotenv() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let env_path = tmp.child(".env");
+    write(
+        &env_path,
+        r#"# database settings
+export DB_HOST=localhost
+DB_PASSWORD="p@ss \"word\"" # the password
+
+API_KEY='multi
+line'
+PORT=8080
+"#,
+    );
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("import", "-R", &pub_path, &env_path, "-o", &encrypted_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    assert_eq!(check_encrypted(&parse_yaml(&read(&encrypted_path))), EncryptionStatus::Encrypted);
+    yage!("decrypt", "-K", &key_path, &encrypted_path)
+        .stdout(
+            r#"# database settings
+DB_HOST: localhost
+DB_PASSWORD: "p@ss \"word\"" # the password
+
+API_KEY: "multi\nline"
+PORT: "8080"
+"#,
+        )
+        .stderr(is_empty());
+}
+
+#[test]
+fn import_json() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let json_path = tmp.child("secrets.json");
+    write(
+        &json_path,
+        r#"{"name": "app", "port": 8080, "tags": ["a", "b"], "users": [{"name": "alice", "pw": "x:y"}]}"#,
+    );
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("import", "-R", &pub_path, &json_path, "-o", &encrypted_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    yage!("decrypt", "-K", &key_path, &encrypted_path)
+        .stdout(
+            "name: app\nport: 8080\ntags:\n  - a\n  - b\nusers:\n  - name: alice\n    pw: \"x:y\"\n",
+        )
+        .stderr(is_empty());
+}
+
+#[test]
+fn import_toml() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let toml_path = tmp.child("secrets.conf");
+    write(
+        &toml_path,
+        "title = \"yes\"\n\n[database]\nhost = \"localhost\"\nports = [8000, 8001]\n",
+    );
+    yage!("import", "--from", "toml", "-R", &pub_path, &toml_path)
+        .stdout(contains("title: yage["))
+        .stderr(is_empty());
+    let encrypted_path = tmp.child("file.enc.yaml");
+    yage!("import", "--from", "toml", "-R", &pub_path, &toml_path, "-o", &encrypted_path);
+    yage!("decrypt", "-K", &key_path, &encrypted_path)
+        .stdout("title: \"yes\"\ndatabase:\n  host: localhost\n  ports:\n    - 8000\n    - 8001\n")
+        .stderr(is_empty());
+}
+
+#[test]
+fn import_unknown_format() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let path = tmp.child("secrets.conf");
+    write(&path, "foo = 1\n");
+    yage_cmd!("import", "-R", &pub_path, &path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("unknown file format — use --from to specify it"));
+}
+
+#[test]
+fn import_invalid_dotenv() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let path = tmp.child("prod.env");
+    write(&path, "FOO=bar\nBAZ='unterminated\n");
+    yage_cmd!("import", "-R", &pub_path, &path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("invalid dotenv line 2: unterminated quote"));
+}
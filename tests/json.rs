@@ -0,0 +1,153 @@
+mod common;
+
+use crate::common::*;
+use assert_fs::prelude::*;
+use predicates::prelude::predicate::str::*;
+
+const JSON_CONTENT: &str = r#"{
+    "name": "app",
+    "port": 8080,
+    "debug": true,
+    "db": {
+        "password": "secret",
+        "hosts": ["a", "b"]
+    },
+    "empty": null
+}
+"#;
+
+const JSON_CONTENT_ENCRYPTED_PATTERN: &str = r#"^\{
+    "name": "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]",
+    "port": "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]",
+    "debug": true,
+    "db": \{
+        "password": "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]",
+        "hosts": \[
+            "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]",
+            "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]"
+        \]
+    \},
+    "empty": null
+\}
+$"#;
+
+// the decrypted file is formatted with the indentation of the original file
+const JSON_CONTENT_DECRYPTED: &str = r#"{
+    "name": "app",
+    "port": 8080,
+    "debug": true,
+    "db": {
+        "password": "secret",
+        "hosts": [
+            "a",
+            "b"
+        ]
+    },
+    "empty": null
+}
+"#;
+
+#[test]
+fn json_encrypt_decrypt_in_place() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let json_path = tmp.child("config.json");
+    write(&json_path, JSON_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &json_path).stdout(is_empty()).stderr(is_empty());
+    read(&json_path).assert(is_match(JSON_CONTENT_ENCRYPTED_PATTERN).unwrap());
+    yage!("check", &json_path).stdout(is_empty()).stderr(is_empty());
+    yage!("decrypt", "-K", &key_path, "-i", &json_path).stdout(is_empty()).stderr(is_empty());
+    assert_eq!(read(&json_path), JSON_CONTENT_DECRYPTED);
+}
+
+#[test]
+fn json_large_integers() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let json_path = tmp.child("config.json");
+    let content = r#"{"max":18446744073709551615,"min":-9223372036854775808,"ratio":0.5}"#;
+    write(&json_path, content);
+    yage!("encrypt", "-R", &pub_path, "-i", &json_path).stdout(is_empty()).stderr(is_empty());
+    yage!("decrypt", "-K", &key_path, &json_path).stdout(content).stderr(is_empty());
+}
+
+#[test]
+fn json_format_option() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let json_path = tmp.child("config.txt");
+    write(&json_path, r#"{"token":"abc","count":3}"#);
+    let encrypted = yage!("encrypt", "-R", &pub_path, "--format", "json", &json_path)
+        .stderr(is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let encrypted_path = tmp.child("config.enc");
+    encrypted_path.write_binary(&encrypted).unwrap();
+    yage!("decrypt", "-K", &key_path, "--format", "json", &encrypted_path)
+        .stdout(r#"{"token":"abc","count":3}"#)
+        .stderr(is_empty());
+}
+
+#[test]
+fn json_check_not_encrypted() {
+    let tmp = temp_dir();
+    let json_path = tmp.child("config.json");
+    write(&json_path, JSON_CONTENT);
+    yage_cmd!("check", &json_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("not encrypted"));
+}
+
+#[test]
+fn json_re_encrypt() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let json_path = tmp.child("config.json");
+    write(&json_path, JSON_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &json_path);
+    let encrypted = read(&json_path);
+    yage!("re-encrypt", "-K", &key_path, "-R", &pub_path, "-i", &json_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    let re_encrypted = read(&json_path);
+    re_encrypted.assert(is_match(JSON_CONTENT_ENCRYPTED_PATTERN).unwrap());
+    assert_ne!(re_encrypted, encrypted);
+    yage!("decrypt", "-K", &key_path, &json_path).stdout(JSON_CONTENT_DECRYPTED);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn json_edit_keeps_missing_final_newline() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let json_path = tmp.child("config.json");
+    write(&json_path, r#"{"token":"secret"}"#);
+    yage!("encrypt", "-R", &pub_path, "-i", &json_path);
+    let editor = "sed -i s/secret/changed/";
+    yage!("edit", "-K", &key_path, "-e", editor, &json_path).stdout(is_empty()).stderr(is_empty());
+    assert!(!read(&json_path).ends_with('\n'));
+    yage!("decrypt", "-K", &key_path, &json_path).stdout(r#"{"token":"changed"}"#);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn json_edit_keeps_unchanged_values() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let json_path = tmp.child("config.json");
+    write(&json_path, JSON_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &json_path);
+    let encrypted = read(&json_path);
+    let editor = "sed -i s/secret/changed/";
+    yage!("edit", "-K", &key_path, "-e", editor, &json_path).stdout(is_empty()).stderr(is_empty());
+    let edited = read(&json_path);
+    let changed: Vec<_> =
+        encrypted.lines().zip(edited.lines()).filter(|(a, b)| a != b).map(|(a, _)| a).collect();
+    assert_eq!(changed.len(), 1);
+    assert!(changed[0].contains("\"password\""));
+    yage!("decrypt", "-K", &key_path, &json_path)
+        .stdout(JSON_CONTENT_DECRYPTED.replace("secret", "changed"));
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use crate::common::*;
+use assert_fs::prelude::*;
+use predicates::prelude::predicate::str::*;
+use predicates::prelude::*;
+
+const YAML_CONTENT: &str = "password: secret\nuser: admin\n";
+
+const NESTED_YAML_CONTENT: &str = "api:
+  db:
+    password: secret
+web:
+  token: abc
+";
+
+const TLS_YAML_CONTENT: &str = "tls.crt: certificate\ntls.key: key\n";
+
+#[test]
+fn k8s_secret_data() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("secrets.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    yage!("k8s-secret", "-K", &key_path, "--name", "api", "--namespace", "prod", &yaml_path)
+        .stdout(
+            "apiVersion: v1
+kind: Secret
+metadata:
+  name: api
+  namespace: prod
+type: Opaque
+data:
+  password: c2VjcmV0
+  user: YWRtaW4=
+",
+        )
+        .stderr(is_empty());
+}
+
+#[test]
+fn k8s_secret_string_data() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("secrets.yaml");
+    write(&yaml_path, NESTED_YAML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    yage!(
+        "k8s-secret",
+        "-K",
+        &key_path,
+        "--name",
+        "api",
+        "--string-data",
+        "--path",
+        "api",
+        "--flatten",
+        "--case",
+        "lower",
+        &yaml_path
+    )
+    .stdout(
+        "apiVersion: v1
+kind: Secret
+metadata:
+  name: api
+type: Opaque
+stringData:
+  db_password: secret
+",
+    )
+    .stderr(is_empty());
+}
+
+#[test]
+fn k8s_secret_tls() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("tls.yaml");
+    write(&yaml_path, TLS_YAML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    yage!(
+        "k8s-secret",
+        "-K",
+        &key_path,
+        "--name",
+        "cert",
+        "--type",
+        "kubernetes.io/tls",
+        &yaml_path
+    )
+    .stdout(contains("type: kubernetes.io/tls\n").and(contains("  tls.key: a2V5\n")))
+    .stderr(is_empty());
+}
+
+#[test]
+fn k8s_secret_tls_missing_key() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("secrets.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    yage_cmd!(
+        "k8s-secret",
+        "-K",
+        &key_path,
+        "--name",
+        "cert",
+        "--type",
+        "kubernetes.io/tls",
+        &yaml_path
+    )
+    .assert()
+    .failure()
+    .stdout(is_empty())
+    .stderr(contains("tls.crt: key required in a kubernetes.io/tls secret"));
+}
+
+#[test]
+fn k8s_secret_invalid_key() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("secrets.yaml");
+    write(&yaml_path, "my/key: value\n");
+    yage!("encrypt", "-R", &pub_path, "-i", &yaml_path);
+    yage_cmd!("k8s-secret", "-K", &key_path, "--name", "api", &yaml_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("my/key: invalid key for a Kubernetes secret"));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::*;
+use predicates::prelude::*;
+use predicates::str::{contains, is_empty};
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures")).join(name)
+}
+
+#[test]
+fn krm_decrypts_selected_resources() {
+    let output = yage_cmd!("krm", "-K", fixture("identity.age"))
+        .pipe_stdin(fixture("krm_resource_list.yaml"))
+        .unwrap()
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        read(&fixture("krm_resource_list_decrypted.yaml"))
+    );
+}
+
+#[test]
+fn krm_kind_option() {
+    // the kinds of the options are added to the kinds of the function config
+    yage_cmd!("krm", "-K", fixture("identity.age"), "--kind", "Deployment")
+        .pipe_stdin(fixture("krm_resource_list.yaml"))
+        .unwrap()
+        .assert()
+        .success()
+        .stdout(
+            contains("password: c2VjcmV0\n")
+                .and(contains("value: 5432\n"))
+                .and(contains("yage[").not()),
+        );
+}
+
+#[test]
+fn krm_name_option() {
+    // no resource is both a Secret, as selected by the function config, and named api
+    yage_cmd!("krm", "-K", fixture("identity.age"), "--name", "api")
+        .pipe_stdin(fixture("krm_resource_list.yaml"))
+        .unwrap()
+        .assert()
+        .success()
+        .stdout(contains("password: \"yage[").and(contains("value: \"yage[")));
+}
+
+#[test]
+fn krm_not_a_resource_list() {
+    yage_cmd!("krm", "-K", fixture("identity.age"))
+        .write_stdin("kind: Secret\n")
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("the input is not a KRM ResourceList"));
+}
+
+#[test]
+fn krm_keeps_block_scalars() {
+    // the sequences in the block scalars are not indented, whatever their header
+    let input = "apiVersion: config.kubernetes.io/v1
+kind: ResourceList
+items:
+- apiVersion: v1
+  kind: ConfigMap
+  metadata:
+    name: ci
+  data:
+    keep: |+
+      steps:
+      - run: a
+
+    script: |2
+      steps:
+      - run: b
+    folded: >2-
+      steps:
+      - run: c
+";
+    yage_cmd!("krm", "-K", fixture("identity.age")).write_stdin(input).assert().success().stdout(
+        contains("keep: \"steps:\\n- run: a\\n\\n\"\n")
+            .and(contains("script: \"steps:\\n- run: b\\n\"\n"))
+            .and(contains("folded: \"steps: - run: c\"\n")),
+    );
+}
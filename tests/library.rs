@@ -0,0 +1,105 @@
+mod common;
+
+use crate::common::*;
+use assert_fs::prelude::*;
+use predicates::prelude::predicate::str::*;
+use serde_json::json;
+use yage::FileFormat;
+use yage::error::YageError;
+use yage::{Document, EncryptionStatus, Vault, x25519};
+
+#[test]
+fn library_open_set_encrypt_save() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("config.yaml");
+    write(&yaml_path, "# the service\ndb:\n  user: app # the user\nport: 8080\n");
+    let vault = Vault::builder().key_file(&key_path).recipient_file(&pub_path).build().unwrap();
+    let mut doc = Document::open(&yaml_path).unwrap();
+    assert_eq!(doc.format(), FileFormat::Yaml);
+    assert_eq!(doc.check(), EncryptionStatus::NotEncrypted);
+    doc.set("db.password", "s3cr3t").unwrap();
+    doc.set("port", 8443).unwrap();
+    doc.encrypt(&vault).unwrap();
+    assert_eq!(doc.check(), EncryptionStatus::Encrypted);
+    doc.save().unwrap();
+    read(&yaml_path)
+        .assert(starts_with("# the service\ndb:\n  user: yage["))
+        .assert(contains("] # the user\n  password: yage["));
+    // the file can be decrypted with the command line
+    yage!("decrypt", "-K", &key_path, &yaml_path)
+        .stdout("# the service\ndb:\n  user: app # the user\n  password: s3cr3t\nport: 8443\n")
+        .stderr(is_empty());
+    let doc = Document::open(&yaml_path).unwrap();
+    assert_eq!(doc.recipients().unwrap(), vault.recipients());
+    assert!(doc.get("db.user").unwrap().as_str().unwrap().starts_with("yage["));
+    assert_eq!(doc.get_decrypted("db.user", &vault).unwrap(), Some(json!("app")));
+    assert_eq!(doc.get_decrypted("db.missing", &vault).unwrap(), None);
+    assert_eq!(
+        doc.to_decrypted_value(&vault).unwrap(),
+        json!({"db": {"user": "app", "password": "s3cr3t"}, "port": 8443})
+    );
+}
+
+#[test]
+fn library_set_formats() {
+    let cases = [
+        (FileFormat::Json, "{\n  \"db\": {\n    \"user\": \"app\"\n  }\n}\n"),
+        (FileFormat::Toml, "[db]\nuser = \"app\" # the user\n"),
+        (FileFormat::Ini, "[db]\nuser = app\n"),
+    ];
+    for (format, text) in cases {
+        let mut doc = Document::parse(text, format).unwrap();
+        doc.set("db.user", "root").unwrap();
+        doc.set("db.password", "s3cr3t").unwrap();
+        assert_eq!(doc.to_value(), json!({"db": {"user": "root", "password": "s3cr3t"}}));
+    }
+    let mut doc = Document::parse("USER=app\n", FileFormat::Dotenv).unwrap();
+    doc.set("PASSWORD", "s3cr3t").unwrap();
+    assert_eq!(doc.to_string(), "USER=app\nPASSWORD=s3cr3t\n");
+    assert!(matches!(doc.set("HOSTS", json!(["a", "b"])), Err(YageError::UnsupportedValue { .. })));
+    let mut doc = Document::parse("db:\n  user: app\n", FileFormat::Yaml).unwrap();
+    assert!(matches!(doc.set("missing.password", "x"), Err(YageError::PathNotFound { .. })));
+}
+
+#[test]
+fn library_recipients() {
+    let identity = x25519::Identity::generate();
+    let other = x25519::Identity::generate();
+    // without recipients, the values are encrypted to the public keys of the identities
+    let vault = Vault::builder().identity(identity.clone()).build().unwrap();
+    let mut doc = Document::parse("password: s3cr3t\n", FileFormat::Yaml).unwrap();
+    doc.encrypt(&vault).unwrap();
+    assert_eq!(doc.recipients().unwrap(), vec![identity.to_public()]);
+    // the recipients of the vault must match the ones of the document
+    let other_vault = Vault::builder()
+        .identity(identity.clone())
+        .recipient_str(other.to_public().to_string())
+        .build()
+        .unwrap();
+    doc.set("token", "abc").unwrap();
+    assert!(matches!(doc.encrypt(&other_vault), Err(YageError::InvalidRecipients)));
+    // unless the values are encrypted again
+    doc.re_encrypt(&other_vault).unwrap();
+    assert_eq!(doc.recipients().unwrap(), vec![other.to_public()]);
+    let other_vault = Vault::builder().identity(other).build().unwrap();
+    assert_eq!(doc.get_decrypted("password", &other_vault).unwrap(), Some(json!("s3cr3t")));
+    doc.decrypt(&other_vault).unwrap();
+    assert_eq!(doc.to_string(), "password: s3cr3t\ntoken: abc\n");
+    // a vault without keys can't encrypt anything
+    assert!(matches!(
+        Document::parse("a: b\n", FileFormat::Yaml).unwrap().encrypt(&Vault::default()),
+        Err(YageError::NoRecipients)
+    ));
+}
+
+#[test]
+fn library_save_as() {
+    let tmp = temp_dir();
+    let mut doc = Document::parse("TOKEN=abc\n", FileFormat::Dotenv).unwrap();
+    assert!(matches!(doc.save(), Err(YageError::NoDocumentPath)));
+    let env_path = tmp.child("app.env");
+    doc.save_as(&env_path).unwrap();
+    assert_eq!(doc.path(), Some(env_path.path()));
+    assert_eq!(read(&env_path), "TOKEN=abc\n");
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use assert_fs::fixture::PathChild;
+use common::*;
+use predicates::prelude::*;
+use predicates::str::contains;
+
+const VALUES: &str = "db:
+  host: db.example.com
+  port: 5432
+  password: \"p@ss: word #1\"
+  empty: \"\"
+";
+
+#[test]
+fn render_template() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let values = tmp.child("values.yaml");
+    write(&values, VALUES);
+    yage!("encrypt", "-i", "-R", &pub_path, &values);
+    let template = tmp.child("app.properties");
+    write(
+        &template,
+        "# {{ \"{{\" }} is escaped\nurl=postgres://{{ db.host }}:{{db.port}}\npassword={{ db.password }}\n",
+    );
+    yage!("render", "-K", &key_path, "--values", &values, &template)
+        .stdout("# {{ is escaped\nurl=postgres://db.example.com:5432\npassword=p@ss: word #1\n");
+    // to a file
+    let output = tmp.child("output.properties");
+    yage!("render", "-K", &key_path, "-f", &values, &template, "-o", &output);
+    assert!(read(&output).contains("password=p@ss: word #1\n"));
+}
+
+#[test]
+fn render_filters() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let values = tmp.child("values.yaml");
+    write(&values, VALUES);
+    yage!("encrypt", "-i", "-R", &pub_path, &values);
+    let template = tmp.child("template");
+    write(
+        &template,
+        "{{ db.user | default(\"admin\") }} {{ db.empty | default('none') }} {{ db.host | required }}
+{{ db.password | yaml }} {{ db.password | json }} {{ db.password | shell }} {{ db.port | json }}
+{{ db | json }}
+",
+    );
+    yage!("render", "-K", &key_path, "-f", &values, &template).stdout(
+        "admin none db.example.com
+\"p@ss: word #1\" \"p@ss: word #1\" 'p@ss: word #1' 5432
+{\"host\":\"db.example.com\",\"port\":5432,\"password\":\"p@ss: word #1\",\"empty\":\"\"}
+",
+    );
+    // the default escaping mode
+    write(&template, "password: {{ db.password }}\nport: {{ db.port | raw }}\n");
+    yage!("render", "-K", &key_path, "-f", &values, "--escape", "yaml", &template)
+        .stdout("password: \"p@ss: word #1\"\nport: 5432\n");
+    // the missing values are empty, unless required
+    write(&template, "user={{ db.user }}\n");
+    yage!("render", "-K", &key_path, "-f", &values, &template).stdout("user=\n");
+    write(&template, "# header\nuser={{ db.user | required }}\n");
+    yage_cmd!("render", "-K", &key_path, "-f", &values, &template)
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(contains("db.user: required value missing or empty"));
+}
+
+#[test]
+fn render_braces_in_literals() {
+    let tmp = temp_dir();
+    let (key_path, _) = create_key(&tmp);
+    let values = tmp.child("values.yaml");
+    write(&values, VALUES);
+    let template = tmp.child("template");
+    // the }} in the quoted literals don't end the placeholders
+    write(
+        &template,
+        "{{ \"}}\" }} {{ '}}' }} {{ db.user | default(\"}}\") }} {{ db.user | default('a\"}}') }}\n",
+    );
+    yage!("render", "-K", &key_path, "-f", &values, &template).stdout("}} }} }} a\"}}\n");
+}
+
+#[test]
+fn render_several_values_files() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let values = tmp.child("values.yaml");
+    write(&values, VALUES);
+    yage!("encrypt", "-i", "-R", &pub_path, &values);
+    let overrides = tmp.child("overrides.json");
+    write(&overrides, "{\"db\": {\"host\": \"localhost\"}}\n");
+    yage!("encrypt", "-i", "-R", &pub_path, &overrides);
+    yage_cmd!("render", "-K", &key_path, "-f", &values, "-f", &overrides, "-")
+        .write_stdin("{{ db.host }}:{{ db.port }}")
+        .assert()
+        .success()
+        .stdout("localhost:5432");
+}
+
+#[test]
+fn render_invalid_template() {
+    let tmp = temp_dir();
+    let (key_path, _) = create_key(&tmp);
+    let values = tmp.child("values.yaml");
+    write(&values, VALUES);
+    for (template, message) in [
+        ("a\nb {{ db.host", "invalid template line 2: unclosed placeholder"),
+        ("{{ db.host | upper }}", "invalid template line 1: unknown filter upper"),
+        ("{{ db.user | default(admin) }}", "the default value must be quoted"),
+        ("{{ db host }}", "invalid path \"db host\""),
+    ] {
+        yage_cmd!("render", "-K", &key_path, "-f", &values, "-")
+            .write_stdin(template)
+            .assert()
+            .failure()
+            .stdout("")
+            .stderr(contains(message).and(contains("db.example.com").not()));
+    }
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use assert_fs::prelude::*;
+use common::*;
+use predicates::str::{contains, is_empty};
+use pretty_assertions::{assert_eq, assert_ne};
+
+#[test]
+fn rotate_remove_recipient() {
+    let tmp = temp_dir();
+    let (key_path1, pub_path1) = create_key(&tmp);
+    let (key_path2, pub_path2) = create_key(&tmp);
+    let yaml_path = tmp.child("file.yaml");
+    write(&yaml_path, YAML_CONTENT);
+    let repo = tmp.child("repo");
+    repo.child("sub").mkdir_all().unwrap();
+    let encrypted_path1 = repo.child("file1.yaml");
+    let encrypted_path2 = repo.child("sub/file2.yml");
+    let ignored_path = repo.child("ignored.yaml");
+    let plain_path = repo.child("plain.yaml");
+    for path in [&encrypted_path1, &encrypted_path2, &ignored_path] {
+        yage!("encrypt", "-R", &pub_path1, "-R", &pub_path2, &yaml_path, "-o", path);
+    }
+    write(&plain_path, YAML_CONTENT);
+    write(&repo.child(".gitignore"), "ignored.yaml\n");
+    let ignored_data = read(&ignored_path);
+    let encrypted_data1 = read(&encrypted_path1);
+
+    yage!("rotate", "-K", &key_path1, "-D", &pub_path2, &repo)
+        .stdout(is_empty())
+        .stderr(contains("2 files re-encrypted"));
+
+    assert_ne!(read(&encrypted_path1), encrypted_data1);
+    assert_eq!(read(&ignored_path), ignored_data);
+    assert_eq!(read(&plain_path), YAML_CONTENT);
+    assert_no_temp_files(&repo);
+    assert_no_temp_files(&repo.child("sub"));
+    assert!(!repo.child("file1.yaml.bak").exists());
+    for path in [&encrypted_path1, &encrypted_path2] {
+        yage!("recipients", "-r", path).stdout(read(&pub_path1)).stderr(is_empty());
+        yage!("decrypt", "-K", &key_path1, path).stdout(YAML_CONTENT).stderr(is_empty());
+        yage_cmd!("decrypt", "-K", &key_path2, path)
+            .assert()
+            .failure()
+            .stdout(is_empty())
+            .stderr(contains("No matching keys found"));
+    }
+}
+
+#[test]
+fn rotate_dry_run() {
+    let (tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    let encrypted_data = read(&encrypted_path);
+    yage!("rotate", "-n", "-K", &key_path, tmp.path())
+        .stdout(is_empty())
+        .stderr(contains("file.enc.yaml: would be re-encrypted to 1 recipients"));
+    assert_eq!(read(&encrypted_path), encrypted_data);
+    assert_no_temp_files(&tmp);
+}
+
+#[test]
+fn rotate_invalid_encrypted_file() {
+    let (tmp, key_path, _, _, encrypted_path) = generate_encrypted_file();
+    let encrypted_data = read(&encrypted_path);
+    let invalid_path = tmp.child("invalid.enc.yaml");
+    write(&invalid_path, "foo: yage[invalid|r:invalid]\n  bar: [\n");
+    yage_cmd!("rotate", "-K", &key_path, tmp.path())
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("invalid.enc.yaml"));
+    assert_eq!(read(&encrypted_path), encrypted_data);
+    // the invalid files without any encrypted value are skipped
+    write(&invalid_path, "foo: bar\n  bar: [\n");
+    yage!("rotate", "-K", &key_path, tmp.path()).stderr(contains("1 files re-encrypted"));
+}
+
+#[test]
+fn rotate_is_transactional() {
+    let (tmp, key_path, _, yaml_path, encrypted_path) = generate_encrypted_file();
+    let (_, other_pub_path) = create_key(&tmp);
+    let other_encrypted_path = tmp.child("other.enc.yaml");
+    yage!("encrypt", "-R", &other_pub_path, &yaml_path, "-o", &other_encrypted_path);
+    let encrypted_data = read(&encrypted_path);
+    let other_encrypted_data = read(&other_encrypted_path);
+    yage_cmd!("rotate", "-K", &key_path, tmp.path())
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("No matching keys found"));
+    assert_eq!(read(&encrypted_path), encrypted_data);
+    assert_eq!(read(&other_encrypted_path), other_encrypted_data);
+    assert_no_temp_files(&tmp);
+}
+
+#[test]
+fn rotate_hidden_files() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let repo = tmp.child("repo");
+    repo.child(".git").mkdir_all().unwrap();
+    let env_path = repo.child(".env");
+    let env_local_path = repo.child(".env.local");
+    let git_path = repo.child(".git/config.yaml");
+    write(&env_path, "DB_PASSWORD=s3cr3t\n");
+    write(&env_local_path, "DB_PASSWORD=l0cal\n");
+    write(&git_path, YAML_CONTENT);
+    for path in [&env_path, &env_local_path, &git_path] {
+        yage!("encrypt", "-R", &pub_path, "-i", path);
+    }
+    let env_data = read(&env_path);
+    let git_data = read(&git_path);
+
+    yage!("rotate", "-K", &key_path, &repo)
+        .stdout(is_empty())
+        .stderr(contains("2 files re-encrypted"));
+
+    assert_ne!(read(&env_path), env_data);
+    assert_eq!(read(&git_path), git_data);
+    yage!("decrypt", "-K", &key_path, &env_path).stdout("DB_PASSWORD=s3cr3t\n");
+    yage!("decrypt", "-K", &key_path, &env_local_path).stdout("DB_PASSWORD=l0cal\n");
+    assert_no_temp_files(&repo);
+}
+
+#[test]
+fn rotate_empty() {
+    yage_cmd!("rotate")
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("error: the following required arguments were not provided"));
+}
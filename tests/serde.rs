@@ -0,0 +1,133 @@
+mod common;
+
+use crate::common::*;
+use assert_fs::prelude::*;
+use serde::Deserialize;
+use yage::error::YageError;
+use yage::{Secret, Vault, from_str_decrypted};
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Level {
+    Debug,
+    Info,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Auth {
+    Token(Secret<String>),
+    Basic { user: String, password: Secret<String> },
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Database {
+    host: String,
+    port: u16,
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    name: String,
+    level: Level,
+    db: Database,
+    replicas: Vec<String>,
+    timeout: Option<f64>,
+    missing: Option<String>,
+    token: Secret<String>,
+    admin: Secret<Database>,
+    backup_token: Option<Secret<String>>,
+    missing_token: Option<Secret<String>>,
+    auth: Vec<Auth>,
+}
+
+const CONFIG: &str = "name: api
+level: info
+db:
+  host: db.local
+  port: 5432
+  password: s3cr3t
+replicas:
+  - a.local
+  - b.local
+timeout: 2.5
+token: abc123
+admin:
+  host: admin.local
+  port: 5433
+  password: adm1n
+backup_token: b4ckup
+auth:
+  - token: t0ken
+  - basic:
+      user: admin
+      password: b4sic
+";
+
+fn encrypted_config() -> (assert_fs::TempDir, Vault, String) {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let yaml_path = tmp.child("config.yaml");
+    write(&yaml_path, CONFIG);
+    let output = yage!("encrypt", "-R", &pub_path, &yaml_path).get_output().stdout.clone();
+    let vault = Vault::builder().key_file(&key_path).build().unwrap();
+    (tmp, vault, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn serde_from_str_decrypted() {
+    let (_tmp, vault, text) = encrypted_config();
+    assert!(!text.contains("s3cr3t"));
+    let config: Config = from_str_decrypted(&text, vault.identities()).unwrap();
+    assert_eq!(config.name, "api");
+    assert_eq!(config.level, Level::Info);
+    assert_eq!(
+        config.db,
+        Database { host: "db.local".into(), port: 5432, password: "s3cr3t".into() }
+    );
+    assert_eq!(config.replicas, vec!["a.local", "b.local"]);
+    assert_eq!(config.timeout, Some(2.5));
+    assert_eq!(config.missing, None);
+    // the secrets are only decrypted on access
+    assert!(config.token.encrypted().as_str().unwrap().starts_with("yage["));
+    assert_eq!(format!("{:?}", config.token), "Secret(…)");
+    assert_eq!(config.token.decrypt(vault.identities()).unwrap(), "abc123");
+    assert_eq!(
+        config.admin.decrypt(vault.identities()).unwrap(),
+        Database { host: "admin.local".into(), port: 5433, password: "adm1n".into() }
+    );
+    // also in the options and the enums
+    let backup_token = config.backup_token.unwrap();
+    assert!(backup_token.encrypted().as_str().unwrap().starts_with("yage["));
+    assert_eq!(backup_token.decrypt(vault.identities()).unwrap(), "b4ckup");
+    assert!(config.missing_token.is_none());
+    let [Auth::Token(token), Auth::Basic { user, password }] = &config.auth[..] else {
+        panic!("unexpected auth: {:?}", config.auth);
+    };
+    assert!(token.encrypted().as_str().unwrap().starts_with("yage["));
+    assert_eq!(token.decrypt(vault.identities()).unwrap(), "t0ken");
+    assert_eq!(user, "admin");
+    assert!(password.encrypted().as_str().unwrap().starts_with("yage["));
+    assert_eq!(password.decrypt(vault.identities()).unwrap(), "b4sic");
+}
+
+#[test]
+fn serde_errors() {
+    let (_tmp, vault, text) = encrypted_config();
+    assert!(matches!(from_str_decrypted::<Config>(&text, &[]), Err(YageError::NoKeys)));
+    let other = Vault::builder().identity(yage::x25519::Identity::generate()).build().unwrap();
+    assert!(matches!(
+        from_str_decrypted::<Config>(&text, other.identities()),
+        Err(YageError::Decrypt(_))
+    ));
+    let error = from_str_decrypted::<Database>("host: db.local\n", vault.identities()).unwrap_err();
+    assert_eq!(error.to_string(), "deserialization error: missing field `port`");
+}
+
+#[test]
+fn serde_secret_in_clear() {
+    // the secrets may also be read in clear, from a file without encrypted values
+    let config: Config = from_str_decrypted(CONFIG, &[]).unwrap();
+    assert_eq!(config.token.decrypt(&[]).unwrap(), "abc123");
+}
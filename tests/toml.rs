@@ -0,0 +1,152 @@
+mod common;
+
+use crate::common::*;
+use assert_fs::prelude::*;
+use predicates::prelude::predicate::str::*;
+
+const TOML_CONTENT: &str = r#"# service configuration
+name = "app"
+port = 8080 # the listening port
+ratio = 0.5
+debug = true
+started = 1979-05-27T07:32:00Z
+
+[db]
+password = "secret"
+hosts = ["a", "b"]
+
+[[users]]
+name = "alice"
+"#;
+
+const TOML_CONTENT_ENCRYPTED_PATTERN: &str = r#"^# service configuration
+name = "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]"
+port = "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]" # the listening port
+ratio = "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]"
+debug = true
+started = 1979-05-27T07:32:00Z
+
+\[db\]
+password = "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]"
+hosts = \["yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]", "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]"\]
+
+\[\[users\]\]
+name = "yage\[[0-9a-zA-Z/=\-+]+\|r:[a-z0-9,]+\]"
+$"#;
+
+#[test]
+fn toml_encrypt_decrypt_in_place() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let toml_path = tmp.child("config.toml");
+    write(&toml_path, TOML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &toml_path).stdout(is_empty()).stderr(is_empty());
+    read(&toml_path).assert(is_match(TOML_CONTENT_ENCRYPTED_PATTERN).unwrap());
+    yage!("check", &toml_path).stdout(is_empty()).stderr(is_empty());
+    yage!("decrypt", "-K", &key_path, "-i", &toml_path).stdout(is_empty()).stderr(is_empty());
+    assert_eq!(read(&toml_path), TOML_CONTENT);
+}
+
+#[test]
+fn toml_special_floats() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let toml_path = tmp.child("config.toml");
+    let content = "a = inf\nb = nan\nc = -inf\nd = 1.5\n";
+    write(&toml_path, content);
+    yage!("encrypt", "-R", &pub_path, "-i", &toml_path).stdout(is_empty()).stderr(is_empty());
+    yage!("decrypt", "-K", &key_path, "-i", &toml_path).stdout(is_empty()).stderr(is_empty());
+    assert_eq!(read(&toml_path), content);
+}
+
+#[test]
+fn toml_format_option() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let toml_path = tmp.child("config.txt");
+    write(&toml_path, "token = \"abc\"\ncount = 3\n");
+    let encrypted = yage!("encrypt", "-R", &pub_path, "--format", "toml", &toml_path)
+        .stderr(is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let encrypted_path = tmp.child("config.enc");
+    encrypted_path.write_binary(&encrypted).unwrap();
+    yage!("decrypt", "-K", &key_path, "--format", "toml", &encrypted_path)
+        .stdout("token = \"abc\"\ncount = 3\n")
+        .stderr(is_empty());
+}
+
+#[test]
+fn toml_check_not_encrypted() {
+    let tmp = temp_dir();
+    let toml_path = tmp.child("config.toml");
+    write(&toml_path, TOML_CONTENT);
+    yage_cmd!("check", &toml_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("not encrypted"));
+}
+
+#[test]
+fn toml_re_encrypt() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let toml_path = tmp.child("config.toml");
+    write(&toml_path, TOML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &toml_path);
+    let encrypted = read(&toml_path);
+    yage!("re-encrypt", "-K", &key_path, "-R", &pub_path, "-i", &toml_path)
+        .stdout(is_empty())
+        .stderr(is_empty());
+    let re_encrypted = read(&toml_path);
+    re_encrypted.assert(is_match(TOML_CONTENT_ENCRYPTED_PATTERN).unwrap());
+    assert_ne!(re_encrypted, encrypted);
+    yage!("decrypt", "-K", &key_path, &toml_path).stdout(TOML_CONTENT);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn toml_edit_keeps_unchanged_values() {
+    let tmp = temp_dir();
+    let (key_path, pub_path) = create_key(&tmp);
+    let toml_path = tmp.child("config.toml");
+    write(&toml_path, TOML_CONTENT);
+    yage!("encrypt", "-R", &pub_path, "-i", &toml_path);
+    let encrypted = read(&toml_path);
+    let editor = "sed -i s/secret/changed/";
+    yage!("edit", "-K", &key_path, "-e", editor, &toml_path).stdout(is_empty()).stderr(is_empty());
+    let edited = read(&toml_path);
+    let changed: Vec<_> =
+        encrypted.lines().zip(edited.lines()).filter(|(a, b)| a != b).map(|(a, _)| a).collect();
+    assert_eq!(changed.len(), 1);
+    assert!(changed[0].starts_with("password = "));
+    yage!("decrypt", "-K", &key_path, &toml_path).stdout(TOML_CONTENT.replace("secret", "changed"));
+}
+
+#[test]
+fn toml_high_entropy_comment() {
+    let tmp = temp_dir();
+    let (_, pub_path) = create_key(&tmp);
+    let toml_path = tmp.child("config.toml");
+    // the # in the strings are not comments
+    write(
+        &toml_path,
+        "url = \"https://host/# this comment contains a secret key A1B2C3D4E5F6G7H8I9J0K1L2M3N4O5P6\"\ntext = '''\n# this comment contains a secret key A1B2C3D4E5F6G7H8I9J0K1L2M3N4O5P6\n'''\n",
+    );
+    yage!("encrypt", "-R", &pub_path, "--on-comment-secret", "fail", &toml_path).stderr(is_empty());
+    write(
+        &toml_path,
+        "name = \"app\" # this comment contains a secret key A1B2C3D4E5F6G7H8I9J0K1L2M3N4O5P6\n",
+    );
+    yage_cmd!("encrypt", "-R", &pub_path, "--on-comment-secret", "fail", &toml_path)
+        .assert()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("config.toml:1:14: high-entropy token detected"));
+    yage_cmd!("edit", "-R", &pub_path, "--on-comment-secret", "encrypt", &toml_path)
+        .assert()
+        .code(2)
+        .stderr(contains("the comments of the toml files can't be encrypted"));
+}